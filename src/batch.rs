@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use crate::brackets::MaritalStatus;
+use crate::cfg::{parse_currency, AnalysisMode};
+use crate::citizen::Citizen;
+use crate::loc::Location;
+use crate::util::{currency_to_bigur, Money};
+
+const INPUT_HEADER: &str = "income,status,source,target";
+const OUTPUT_HEADER: &str = "income,status,source,target,equivalent_income,home_taxes,target_taxes";
+
+/// Analyzes a single row, with everything in the source's currency. There are no expenses, so only
+/// the taxes differ.
+fn analyze_row(row: &str) -> Result<String, String> {
+    let fields: Vec<_> = row.split(',').map(str::trim).collect();
+    if fields.len() != 4 {
+        return Err(format!(
+            "Expected the 4 fields of {:?}, not {}.",
+            INPUT_HEADER,
+            fields.len()
+        ));
+    }
+    let income = Money(currency_to_bigur(&parse_currency(fields[0])?)?);
+    let status: MaritalStatus = fields[1].parse()?;
+    let home: Location = fields[2].parse()?;
+    let target: Location = fields[3].parse()?;
+    let citizen = Citizen::new(income, status, home);
+    let equivalent_income =
+        citizen.estimate_equivalent_income_at(&target, AnalysisMode::PostTax)?;
+    Ok(format!(
+        "{},{},{},{},{},{},{}",
+        citizen.income,
+        fields[1],
+        fields[2],
+        fields[3],
+        Money(equivalent_income),
+        Money(citizen.calc_taxes()),
+        Money(citizen.calc_taxes_at(&target)),
+    ))
+}
+
+/// Reads a CSV with the columns of `INPUT_HEADER`, and gives back a CSV with each row's results
+/// appended. Fields aren't quoted, so amounts can't be grouped with commas.
+pub fn analyze_csv(path: impl AsRef<Path>) -> Result<String, String> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read scenarios from {:?}: {}", path, e))?;
+    let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+    match lines.next() {
+        Some(header) if header.trim() == INPUT_HEADER => {}
+        _ => {
+            return Err(format!(
+                "Expected {:?} to start with the header {:?}.",
+                path, INPUT_HEADER
+            ))
+        }
+    }
+    let mut output = vec![OUTPUT_HEADER.to_owned()];
+    for (idx, row) in lines.enumerate() {
+        let row = analyze_row(row).map_err(|e| format!("Row {}: {}", idx + 1, e))?;
+        output.push(row);
+    }
+    Ok(output.join("\n") + "\n")
+}
@@ -2,13 +2,19 @@ use num::{
     bigint::BigUint,
     traits::{One, Zero},
 };
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     iter::Extend,
     ops::{Bound, RangeBounds},
+    path::Path,
+    sync::Mutex,
 };
 
-use crate::util::{cast_ratio, ApproxRatio, BigUR, UR64};
+use crate::util::{cast_ratio, parse_rate, ApproxPercent, ApproxRatio, BigUR, UR64};
+
+/// The log target of the bracket-by-bracket trace, so it can be silenced separately.
+pub const WALK_LOG_TARGET: &str = "equinc::brackets::walk";
 
 /// Assumes the list of separators are inclusive.
 pub fn multibound_to_opts_iter<I, Iter, T>(i: I) -> impl Iterator<Item = (Option<T>, Option<T>)>
@@ -23,6 +29,8 @@ where
     bot_iter.zip(top_iter)
 }
 
+type BoundCtor<T> = fn(T) -> Bound<T>;
+
 /// Assumes the list of separators are inclusive.
 pub fn multibound_to_bounds_iter<I, Iter, T>(
     i: I,
@@ -35,7 +43,7 @@ where
     Iter: Clone + Iterator<Item = T>,
 {
     // Enum variant inference forces this to be typed since the assumed type is too stringent.
-    let (bot_map, top_map): (fn(T) -> Bound<T>, fn(T) -> Bound<T>) = if inclusive_bounds {
+    let (bot_map, top_map): (BoundCtor<T>, BoundCtor<T>) = if inclusive_bounds {
         (Bound::Included, Bound::Excluded)
     } else {
         (Bound::Excluded, Bound::Included)
@@ -43,7 +51,7 @@ where
 
     let opt_iter = multibound_to_opts_iter(i);
 
-    let opt_to_bound = move |opt, conv: fn(T) -> Bound<T>| match opt {
+    let opt_to_bound = move |opt, conv: BoundCtor<T>| match opt {
         Some(v) => conv(v),
         None => Bound::Unbounded,
     };
@@ -57,15 +65,27 @@ where
     opt_iter.map(process_bounds)
 }
 
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+/// Serializes to the same names that the parser accepts.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum MaritalStatus {
     Single,
     Joint,
     Separate,
+    #[serde(rename = "head")]
     HeadOfHousehold,
     // TODO California seems to have a "Widower with child" status, so what about other statuses?
 }
 
+impl MaritalStatus {
+    pub const ALL: [MaritalStatus; 4] = [
+        MaritalStatus::Single,
+        MaritalStatus::Joint,
+        MaritalStatus::Separate,
+        MaritalStatus::HeadOfHousehold,
+    ];
+}
+
 impl std::str::FromStr for MaritalStatus {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -82,12 +102,46 @@ impl std::str::FromStr for MaritalStatus {
 
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
 enum Side {
-    LHS,
-    RHS,
+    Lhs,
+    Rhs,
     Both,
 }
 
-#[derive(Debug, Clone)]
+/// A single difference between two schedules, going from the old one to the new one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BracketDiff {
+    AddedSeparator(BigUR),
+    RemovedSeparator(BigUR),
+    /// The rate of the band of income above `above`, or starting at zero if there is none.
+    RateChanged {
+        above: Option<BigUR>,
+        old: UR64,
+        new: UR64,
+    },
+}
+
+impl std::fmt::Display for BracketDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percent = |rate: &UR64| ApproxPercent(*rate);
+        match self {
+            Self::AddedSeparator(sep) => {
+                write!(f, "Added separator at {}", ApproxRatio(sep.clone()))
+            }
+            Self::RemovedSeparator(sep) => {
+                write!(f, "Removed separator at {}", ApproxRatio(sep.clone()))
+            }
+            Self::RateChanged { above, old, new } => {
+                match above {
+                    Some(above) => write!(f, "Rate above {}", ApproxRatio(above.clone()))?,
+                    None => write!(f, "Bottom rate")?,
+                }
+                write!(f, " changed from {} to {}", percent(old), percent(new))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TaxBrackets {
     // n - 1 elements -- missing first
     /// The value is a vec of the bigint that is the inclusive upper bound of the tax bracket.
@@ -113,7 +167,14 @@ impl TaxBrackets {
     ) -> Self {
         let separators: Vec<_> = separators.map(Into::into).collect();
         let rates: Vec<_> = rates.map(Into::into).collect();
-        assert!(separators.len() + 1 == rates.len());
+        // Having a rate for every band means there's always at least one, so every income lands in
+        // some band when walking them.
+        assert!(
+            separators.len() + 1 == rates.len(),
+            "Expected one more rate than the {} separators, but got {} rates.",
+            separators.len(),
+            rates.len()
+        );
         let flats = {
             let mut flats = Vec::with_capacity(rates.len());
             for bracket in 0..rates.len() {
@@ -140,6 +201,133 @@ impl TaxBrackets {
         }
     }
 
+    /// Approximates a later year's brackets, with every separator scaled by an inflation `factor`
+    /// like `1.02`, and the flats worked out again from them.
+    pub fn indexed(&self, factor: UR64) -> TaxBrackets {
+        assert!(!factor.is_zero(), "Expected a positive inflation factor.");
+        let factor: BigUR = cast_ratio(factor);
+        let separators = self
+            .separators
+            .iter()
+            .map(|sep| sep.clone() * factor.clone());
+        Self::new(separators, self.rates.iter().cloned())
+    }
+
+    /// How much more income fits in the band that `gross` falls in, or `None` in the top band.
+    pub fn headroom_in_bracket(&self, gross: &BigUR) -> Option<BigUR> {
+        // Separators are inclusive, so a gross exactly on one has no room left.
+        self.separators
+            .iter()
+            .find(|sep| *sep >= gross)
+            .map(|sep| sep.clone() - gross.clone())
+    }
+
+    /// The rate on the next unit earned above `gross`.
+    pub fn marginal_rate(&self, gross: &BigUR) -> UR64 {
+        self.rate_above(Some(gross))
+    }
+
+    /// Where the top band starts, if there's more than one band.
+    pub fn top_separator(&self) -> Option<&BigUR> {
+        self.separators.last()
+    }
+
+    /// The rate of the band directly above `floor`, or of the bottom band if there is none.
+    fn rate_above(&self, floor: Option<&BigUR>) -> UR64 {
+        let idx = floor.map_or(0, |floor| {
+            self.separators.iter().filter(|s| *s <= floor).count()
+        });
+        self.rates[idx]
+    }
+
+    /// Lists what changed going from `self` to `other`. Bands that changed rate together are
+    /// reported as one.
+    pub fn diff(&self, other: &Self) -> Vec<BracketDiff> {
+        let mut diffs = vec![];
+        let mut all_separators: Vec<_> = self
+            .separators
+            .iter()
+            .chain(other.separators.iter())
+            .collect();
+        all_separators.sort();
+        all_separators.dedup();
+        for sep in all_separators.iter() {
+            match (
+                self.separators.contains(sep),
+                other.separators.contains(sep),
+            ) {
+                (true, false) => diffs.push(BracketDiff::RemovedSeparator((*sep).clone())),
+                (false, true) => diffs.push(BracketDiff::AddedSeparator((*sep).clone())),
+                _ => {}
+            }
+        }
+
+        let floors = std::iter::once(None).chain(all_separators.into_iter().map(Some));
+        let mut prev_rates = None;
+        for floor in floors {
+            let rates = (self.rate_above(floor), other.rate_above(floor));
+            if rates.0 != rates.1 && prev_rates != Some(rates) {
+                diffs.push(BracketDiff::RateChanged {
+                    above: floor.cloned(),
+                    old: rates.0,
+                    new: rates.1,
+                });
+            }
+            prev_rates = Some(rates);
+        }
+        diffs
+    }
+
+    /// Describes everything that makes the brackets unusable, like rates that would tax away the
+    /// whole income or a gross that doesn't come back from its own net.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if self.separators.windows(2).any(|pair| pair[0] >= pair[1]) {
+            problems.push("separators are not increasing".to_owned());
+        }
+        if let Some(rate) = self.rates.iter().find(|rate| **rate >= UR64::one()) {
+            problems.push(format!("rate {} is not below 1", ApproxRatio(*rate)));
+        }
+        if self.flats.first().is_some_and(|flat| !flat.is_zero()) {
+            problems.push("the bottom band has taxes below it".to_owned());
+        }
+        if self.flats.windows(2).any(|pair| pair[0] > pair[1]) {
+            problems.push("flats are decreasing".to_owned());
+        }
+        if self
+            .post_tax_separators()
+            .windows(2)
+            .any(|pair| pair[0] >= pair[1])
+        {
+            problems.push("post-tax separators are not increasing".to_owned());
+        }
+        if !problems.is_empty() {
+            // The round trip means nothing with broken brackets.
+            return problems;
+        }
+
+        let one = BigUR::one();
+        let mut samples = vec![BigUR::zero()];
+        for sep in self.separators.iter() {
+            samples.push(sep.clone());
+            samples.push(sep.clone() + one.clone());
+        }
+        if let Some(last) = self.separators.last() {
+            samples.push(last.clone() * BigUint::from(2u8));
+        }
+        for gross in samples {
+            let round_trip = self.calc_gross(&self.calc_net(&gross));
+            if round_trip != gross {
+                problems.push(format!(
+                    "gross {} comes back as {} from its net",
+                    ApproxRatio(gross),
+                    ApproxRatio(round_trip)
+                ));
+            }
+        }
+        problems
+    }
+
     fn taxation_info<'a>(&'a self) -> impl 'a + Clone + Iterator<Item = (&'a BigUR, &'a UR64)> {
         self.flats.iter().zip(self.rates.iter())
     }
@@ -148,7 +336,7 @@ impl TaxBrackets {
         let bounds_and_taxation_info =
             multibound_to_bounds_iter(self.separators.iter(), true).zip(self.taxation_info());
         for (bound, taxation_info) in bounds_and_taxation_info {
-            log::info!("Bracket {:?}", bound);
+            log::info!(target: WALK_LOG_TARGET, "Bracket {:?}", bound);
             if bound.contains(gross) {
                 let start_bound: Bound<&BigUR> = bound.start_bound();
                 let amount_over = match start_bound {
@@ -158,17 +346,21 @@ impl TaxBrackets {
                 let (flat, rate) = taxation_info;
                 let taxes = flat + amount_over.clone() * cast_ratio(*rate);
                 log::info!(
+                    target: WALK_LOG_TARGET,
                     "Taxes for {} calced to be {} with rate {} on {} and bump {}.",
                     ApproxRatio(gross.clone()),
                     ApproxRatio(taxes.clone()),
-                    ApproxRatio(rate.clone()),
+                    ApproxRatio(*rate),
                     ApproxRatio(amount_over.clone()),
                     ApproxRatio(flat.clone()),
                 );
                 return taxes;
             }
         }
-        unreachable!("All bounds should be included by `multibound_to_bounds_iter`");
+        panic!(
+            "Internal error: no bracket contains the gross {}, though construction guarantees one.",
+            ApproxRatio(gross.clone())
+        );
     }
 
     fn calc_net(&self, gross: &BigUR) -> BigUR {
@@ -179,6 +371,11 @@ impl TaxBrackets {
         gross.clone() - taxed
     }
 
+    /// The net incomes at which each band starts, which is what `calc_gross` walks.
+    pub fn post_tax_separators(&self) -> Vec<BigUR> {
+        self.separators_post_tax().collect()
+    }
+
     fn separators_post_tax<'a>(&'a self) -> impl 'a + Clone + Iterator<Item = BigUR> {
         assert!(self.separators.len() + 1 == self.flats.len());
         self.separators
@@ -188,14 +385,14 @@ impl TaxBrackets {
     }
 
     fn calc_gross(&self, net: &BigUR) -> BigUR {
-        log::info!("Net                : {}", ApproxRatio(net.clone()));
+        log::info!(target: WALK_LOG_TARGET, "Net                : {}", ApproxRatio(net.clone()));
         // Map pre tax ranges to post tax ranges
         let separators = self.separators_post_tax();
 
         let bounds_and_taxation_info =
             multibound_to_bounds_iter(separators, true).zip(self.taxation_info());
         for (bound, taxation_info) in bounds_and_taxation_info {
-            log::info!("Bracket {:?}", bound);
+            log::info!(target: WALK_LOG_TARGET, "Bracket {:?}", bound);
             if bound.contains(net) {
                 let (over_amount, prev_bucket) = match bound.start_bound() {
                     // No matter if the end bound is present or absent, the value is less than the top bound,
@@ -204,21 +401,37 @@ impl TaxBrackets {
                     // It's contained, so we tax everything in the range
                     Bound::Excluded(n) | Bound::Included(n) => (net.clone() - n.clone(), n.clone()),
                 };
-                log::info!("Over amount        : {}", ApproxRatio(over_amount.clone()));
+                log::info!(
+                    target: WALK_LOG_TARGET,
+                    "Over amount        : {}",
+                    ApproxRatio(over_amount.clone())
+                );
                 let (flat, rate) = taxation_info;
                 let percentage_of_gross = UR64::one() - rate;
-                log::info!("Flat deduction     : {}", ApproxRatio(flat.clone()));
-                log::info!("Marginal rate      : {}", ApproxRatio(rate.clone()));
                 log::info!(
+                    target: WALK_LOG_TARGET,
+                    "Flat deduction     : {}",
+                    ApproxRatio(flat.clone())
+                );
+                log::info!(target: WALK_LOG_TARGET, "Marginal rate      : {}", ApproxRatio(*rate));
+                log::info!(
+                    target: WALK_LOG_TARGET,
                     "Percentage of gross: {}",
-                    ApproxRatio(percentage_of_gross.clone())
+                    ApproxRatio(percentage_of_gross)
                 );
                 let gross = flat + prev_bucket + over_amount / cast_ratio(percentage_of_gross);
-                log::info!("Gross              : {}", ApproxRatio(gross.clone()));
+                log::info!(
+                    target: WALK_LOG_TARGET,
+                    "Gross              : {}",
+                    ApproxRatio(gross.clone())
+                );
                 return gross;
             }
         }
-        unreachable!("All bounds should be included in the for loop.");
+        panic!(
+            "Internal error: no bracket contains the net {}, though construction guarantees one.",
+            ApproxRatio(net.clone())
+        );
     }
 
     fn merge(lhs: Self, rhs: Self) -> Self {
@@ -246,16 +459,16 @@ impl TaxBrackets {
                     lhs_brackets_iter.next();
                     rhs_brackets_iter.next();
                 } else if lhs_bracket < rhs_bracket {
-                    merged_separators.push((Side::LHS, *lhs_bracket));
+                    merged_separators.push((Side::Lhs, *lhs_bracket));
                     lhs_brackets_iter.next();
                 } else {
-                    merged_separators.push((Side::RHS, *rhs_bracket));
+                    merged_separators.push((Side::Rhs, *rhs_bracket));
                     rhs_brackets_iter.next();
                 }
             }
             // Either lhs or rhs is empty, but it's hard to do this properly, so let's let someone else take care of it.
-            merged_separators.extend(lhs_brackets_iter.map(|b| (Side::LHS, b)));
-            merged_separators.extend(rhs_brackets_iter.map(|b| (Side::RHS, b)));
+            merged_separators.extend(lhs_brackets_iter.map(|b| (Side::Lhs, b)));
+            merged_separators.extend(rhs_brackets_iter.map(|b| (Side::Rhs, b)));
 
             merged_separators
                 .into_iter()
@@ -276,16 +489,16 @@ impl TaxBrackets {
             let mut curr_rhs = *rhs_rates_iter
                 .next()
                 .expect("the rhs tax brackets have at least one bracket.");
-            merged_rates.push(curr_lhs.clone() + curr_rhs.clone());
+            merged_rates.push(curr_lhs + curr_rhs);
 
             for side in merge_order {
                 match side {
-                    Side::LHS => {
+                    Side::Lhs => {
                         curr_lhs = *lhs_rates_iter
                             .next()
                             .expect("Number of tax rates in the lhs to be correct.");
                     }
-                    Side::RHS => {
+                    Side::Rhs => {
                         curr_rhs = *rhs_rates_iter
                             .next()
                             .expect("Number of tax rates in the rhs to be correct.");
@@ -309,8 +522,174 @@ impl TaxBrackets {
     }
 }
 
+/// A rate in a data file, written as `"10%"`, `"0.10"`, or `[10, 100]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum RateData {
+    Text(String),
+    Fraction(u64, u64),
+}
+
+impl RateData {
+    fn to_rate(&self) -> Result<UR64, String> {
+        match self {
+            Self::Text(s) => parse_rate(s),
+            Self::Fraction(_, 0) => Err(format!("The rate {:?} has a zero denominator.", self)),
+            Self::Fraction(numer, denom) => Ok(UR64::new(*numer, *denom)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TaxBracketsData {
+    separators: Vec<u64>,
+    rates: Vec<RateData>,
+    /// Taken off the income before the brackets apply, separately from any standard deduction.
+    #[serde(default)]
+    personal_exemption: u64,
+    /// Taken off the income for each dependent, on top of the personal exemption.
+    #[serde(default)]
+    dependent_exemption: u64,
+    /// How the exemptions shrink for higher incomes, if they do.
+    #[serde(default)]
+    exemption_phaseout: Option<PhaseoutData>,
+}
+
+/// An exemption that shrinks by `rate` for every unit of income above `threshold`, until it's gone.
+#[derive(Debug, Clone, Deserialize)]
+struct PhaseoutData {
+    threshold: u64,
+    rate: RateData,
+}
+
+/// Lays out brackets over the taxable income as brackets over the gross income, when `exemption`
+/// is taken off first and shrinks by `rate` for every unit of gross above `threshold`. While it
+/// shrinks, each unit earned adds `1 + rate` to the taxable income, so the rates in that stretch
+/// are scaled up by as much, like the UK's personal allowance.
+///
+/// The threshold can't be below the exemption, so the taxable income never has to start over.
+fn phase_out_exemption(
+    separators: &[BigUR],
+    rates: &[UR64],
+    exemption: &BigUR,
+    threshold: &BigUR,
+    rate: UR64,
+) -> (Vec<BigUR>, Vec<UR64>) {
+    let one = BigUR::one();
+    let phaseout_rate: BigUR = cast_ratio(rate);
+    let end = threshold.clone() + exemption.clone() / phaseout_rate.clone();
+    // The gross income where the taxable income reaches `taxable`.
+    let gross_at = |taxable: &BigUR| {
+        if *taxable <= threshold.clone() - exemption.clone() {
+            taxable.clone() + exemption.clone()
+        } else if *taxable <= end {
+            (taxable.clone() + exemption.clone() + phaseout_rate.clone() * threshold.clone())
+                / (one.clone() + phaseout_rate.clone())
+        } else {
+            taxable.clone()
+        }
+    };
+    // The taxable income at `gross`, and how much more of it each unit of gross adds.
+    let taxable_at = |gross: &BigUR| {
+        if *gross <= *exemption {
+            (BigUR::zero(), UR64::zero())
+        } else if *gross <= *threshold {
+            (gross.clone() - exemption.clone(), UR64::one())
+        } else if *gross < end {
+            let taxable = (one.clone() + phaseout_rate.clone()) * gross.clone()
+                - exemption.clone()
+                - phaseout_rate.clone() * threshold.clone();
+            (taxable, UR64::one() + rate)
+        } else {
+            (gross.clone(), UR64::one())
+        }
+    };
+
+    let mut gross_separators: Vec<_> = separators.iter().map(gross_at).collect();
+    gross_separators.extend(vec![exemption.clone(), threshold.clone(), end.clone()]);
+    gross_separators.retain(|sep| !sep.is_zero());
+    gross_separators.sort();
+    gross_separators.dedup();
+
+    let floors = std::iter::once(BigUR::zero()).chain(gross_separators.iter().cloned());
+    let probes = floors.zip(
+        gross_separators
+            .iter()
+            .map(Some)
+            .chain(std::iter::once(None)),
+    );
+    let gross_rates = probes
+        .map(|(floor, ceiling)| {
+            let probe = match ceiling {
+                Some(ceiling) => (floor + ceiling.clone()) / BigUR::from_integer(2u8.into()),
+                None => floor + one.clone(),
+            };
+            let (taxable, slope) = taxable_at(&probe);
+            let band = separators.iter().filter(|sep| **sep < taxable).count();
+            rates[band] * slope
+        })
+        .collect();
+    (gross_separators, gross_rates)
+}
+
+/// An exemption is an untaxed band below all the others, which pushes the rest up by as much.
+fn exempt_below(
+    separators: Vec<BigUR>,
+    rates: Vec<UR64>,
+    exemption: &BigUR,
+) -> (Vec<BigUR>, Vec<UR64>) {
+    if exemption.is_zero() {
+        return (separators, rates);
+    }
+    let shifted = separators.into_iter().map(|sep| sep + exemption.clone());
+    let separators = std::iter::once(exemption.clone()).chain(shifted).collect();
+    let rates = std::iter::once(UR64::zero()).chain(rates).collect();
+    (separators, rates)
+}
+
+/// One line per band, from the bottom up, with the marginal rate of each.
+impl std::fmt::Display for TaxBrackets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let percent = |rate: &UR64| ApproxPercent(*rate);
+        let floors = std::iter::once(None).chain(self.separators.iter().map(Some));
+        let ceilings = self
+            .separators
+            .iter()
+            .map(Some)
+            .chain(std::iter::once(None));
+        for ((floor, ceiling), rate) in floors.zip(ceilings).zip(self.rates.iter()) {
+            let floor = ApproxRatio(floor.cloned().unwrap_or_else(BigUR::zero));
+            match ceiling {
+                Some(ceiling) => writeln!(
+                    f,
+                    "{} to {}: {}",
+                    floor,
+                    ApproxRatio(ceiling.clone()),
+                    percent(rate)
+                )?,
+                None => writeln!(f, "Above {}: {}", floor, percent(rate))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Warns the first time a tax system turns out to be missing `status`, since it then doesn't tax
+/// them at all. A status that's meant to be untaxed should have a band at 0% instead.
+fn warn_missing_status(status: MaritalStatus) {
+    static WARNED: Mutex<Vec<MaritalStatus>> = Mutex::new(Vec::new());
+    let mut warned = WARNED.lock().unwrap_or_else(|e| e.into_inner());
+    if !warned.contains(&status) {
+        log::warn!(
+            "A tax system has no brackets for {:?}, so it doesn't tax them at all.",
+            status
+        );
+        warned.push(status);
+    }
+}
+
 // TODO check if taxation is bijective. I think it is, but not sure.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TaxSystem(HashMap<MaritalStatus, TaxBrackets>);
 
 impl TaxSystem {
@@ -334,43 +713,202 @@ impl TaxSystem {
         Self(brackets)
     }
 
+    /// Loads a JSON file mapping each marital status to its `separators` and `rates`, along with an
+    /// optional `personal_exemption` and `dependent_exemption`, which apply to `dependents`.
+    pub fn from_file(path: impl AsRef<Path>, dependents: u32) -> Result<Self, String> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read tax data from {:?}: {}", path, e))?;
+        let data: HashMap<MaritalStatus, TaxBracketsData> = serde_json::from_str(&contents)
+            .map_err(|e| format!("Could not parse tax data from {:?}: {}", path, e))?;
+
+        let mut brackets_by_status = HashMap::with_capacity(data.len());
+        for (status, brackets) in data {
+            let rates = brackets
+                .rates
+                .iter()
+                .map(RateData::to_rate)
+                .collect::<Result<Vec<_>, _>>()?;
+            if rates.is_empty() {
+                return Err(format!(
+                    "Expected at least one rate for {:?} in {:?}.",
+                    status, path
+                ));
+            }
+            // A rate of 100% or more leaves nothing of the income, so there's no gross to solve for.
+            if let Some(rate) = rates.iter().find(|rate| **rate >= UR64::one()) {
+                return Err(format!(
+                    "Expected rates below 100% for {:?} in {:?}, not {}.",
+                    status, path, rate
+                ));
+            }
+            if brackets.separators.windows(2).any(|w| w[0] >= w[1]) {
+                return Err(format!(
+                    "Expected increasing separators for {:?} in {:?}.",
+                    status, path
+                ));
+            }
+            if brackets.separators.len() + 1 != rates.len() {
+                return Err(format!(
+                    "Expected one more rate than separators for {:?} in {:?}.",
+                    status, path
+                ));
+            }
+            let exemption = brackets
+                .dependent_exemption
+                .checked_mul(dependents.into())
+                .and_then(|e| e.checked_add(brackets.personal_exemption))
+                .ok_or_else(|| {
+                    format!("The exemption for {:?} in {:?} is too large.", status, path)
+                })?;
+            let separators: Vec<_> = brackets
+                .separators
+                .iter()
+                .map(|sep| BigUR::from_integer((*sep).into()))
+                .collect();
+            let exemption = BigUR::from_integer(exemption.into());
+            let phaseout = match brackets.exemption_phaseout.as_ref() {
+                Some(phaseout) => Some((phaseout.threshold, phaseout.rate.to_rate()?)),
+                None => None,
+            };
+            let (separators, rates) = match phaseout {
+                _ if exemption.is_zero() => (separators, rates),
+                Some((threshold, rate)) if !rate.is_zero() => {
+                    let threshold = BigUR::from_integer(threshold.into());
+                    if threshold < exemption {
+                        return Err(format!(
+                            "Expected the exemption phaseout for {:?} in {:?} to start above the exemption.",
+                            status, path
+                        ));
+                    }
+                    let (separators, rates) =
+                        phase_out_exemption(&separators, &rates, &exemption, &threshold, rate);
+                    // The stretch where the exemption shrinks is taxed at scaled-up rates, which
+                    // can reach 100% even when none of the rates in the file do.
+                    if let Some(rate) = rates.iter().find(|rate| **rate >= UR64::one()) {
+                        return Err(format!(
+                            "Expected the exemption phaseout for {:?} in {:?} to keep rates below 100%, not {}.",
+                            status, path, rate
+                        ));
+                    }
+                    (separators, rates)
+                }
+                _ => exempt_below(separators, rates, &exemption),
+            };
+            brackets_by_status.insert(status, (separators, rates));
+        }
+        Ok(Self::from_brackets_map(brackets_by_status))
+    }
+
+    /// Like `new`, but from exact separators, like the ones `to_brackets_map` gives back.
+    pub fn from_brackets_map(
+        brackets_by_status: HashMap<MaritalStatus, (Vec<BigUR>, Vec<UR64>)>,
+    ) -> Self {
+        let brackets = brackets_by_status
+            .into_iter()
+            .map(|(k, (separators, rates))| {
+                (
+                    k,
+                    TaxBrackets::new(separators.into_iter(), rates.into_iter()),
+                )
+            })
+            .collect();
+        Self(brackets)
+    }
+
+    /// The separators and rates of every status, in the form `new` takes them. Merged systems can
+    /// have separators that aren't whole, so they're exact ratios.
+    pub fn to_brackets_map(&self) -> HashMap<MaritalStatus, (Vec<BigUR>, Vec<UR64>)> {
+        self.0
+            .iter()
+            .map(|(k, brackets)| (*k, (brackets.separators.clone(), brackets.rates.clone())))
+            .collect()
+    }
+
+    /// The same system with `deduction` of each status taken off the income before the brackets.
+    /// Taking it off here rather than from the gross keeps it to this system once it's merged with
+    /// the others.
+    pub fn with_deduction(&self, deduction: impl Fn(MaritalStatus) -> Option<BigUR>) -> Self {
+        let brackets_by_status = self
+            .to_brackets_map()
+            .into_iter()
+            .map(|(status, (separators, rates))| match deduction(status) {
+                Some(deduction) => (status, exempt_below(separators, rates, &deduction)),
+                None => (status, (separators, rates)),
+            })
+            .collect();
+        Self::from_brackets_map(brackets_by_status)
+    }
+
+    /// The same system with the brackets of every status indexed by `factor`.
+    pub fn indexed(&self, factor: UR64) -> Self {
+        Self(
+            self.0
+                .iter()
+                .map(|(status, brackets)| (*status, brackets.indexed(factor)))
+                .collect(),
+        )
+    }
+
     pub fn flat(rate: UR64) -> Self {
-        let statuses = [
-            MaritalStatus::Single,
-            MaritalStatus::Separate,
-            MaritalStatus::Joint,
-            MaritalStatus::HeadOfHousehold,
-        ];
+        let statuses = MaritalStatus::ALL;
         let tax_brackets = TaxBrackets::base(Vec::<u64>::new().into_iter(), vec![rate].into_iter());
         let map = statuses.iter().map(|k| (*k, tax_brackets.clone()));
         Self(map.collect())
     }
 
+    /// The problems of the brackets of every status, labelled by the status.
+    pub fn problems(&self) -> Vec<String> {
+        MaritalStatus::ALL
+            .iter()
+            .filter_map(|status| Some((status, self.0.get(status)?)))
+            .flat_map(|(status, brackets)| {
+                brackets
+                    .problems()
+                    .into_iter()
+                    .map(move |problem| format!("{:?}: {}", status, problem))
+            })
+            .collect()
+    }
+
+    pub fn brackets(&self, status: MaritalStatus) -> Option<&TaxBrackets> {
+        self.0.get(&status)
+    }
+
+    /// Like `brackets`, but warns when the status is missing.
+    fn brackets_or_warn(&self, status: MaritalStatus) -> Option<&TaxBrackets> {
+        let brackets = self.0.get(&status);
+        if brackets.is_none() {
+            warn_missing_status(status);
+        }
+        brackets
+    }
+
     pub fn calc_taxes(&self, gross: &BigUR, status: MaritalStatus) -> BigUR {
-        self.0
-            .get(&status)
+        self.brackets_or_warn(status)
             .map_or_else(BigUR::zero, |b| b.calc_taxes(gross))
     }
 
+    /// The taxes for each year of a stream of annual incomes, with every year taxed on its own.
+    pub fn calc_taxes_stream(&self, incomes: &[BigUR], status: MaritalStatus) -> Vec<BigUR> {
+        incomes
+            .iter()
+            .map(|gross| self.calc_taxes(gross, status))
+            .collect()
+    }
+
     pub fn calc_net(&self, gross: &BigUR, status: MaritalStatus) -> BigUR {
-        self.0
-            .get(&status)
+        self.brackets_or_warn(status)
             .map_or_else(|| gross.clone(), |b| b.calc_net(gross))
     }
 
     pub fn calc_gross(&self, net: &BigUR, status: MaritalStatus) -> BigUR {
-        self.0
-            .get(&status)
+        self.brackets_or_warn(status)
             .map_or_else(|| net.clone(), |b| b.calc_gross(net))
     }
 
     pub fn merge(mut lhs: TaxSystem, mut rhs: TaxSystem) -> Self {
-        let statuses = [
-            MaritalStatus::Single,
-            MaritalStatus::Separate,
-            MaritalStatus::Joint,
-            MaritalStatus::HeadOfHousehold,
-        ];
+        let statuses = MaritalStatus::ALL;
         let new_tax_brackets =
             statuses
                 .iter()
@@ -382,3 +920,317 @@ impl TaxSystem {
         Self(new_tax_brackets.collect())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// The most bands and the widest band a generated schedule gets, in whole currency units.
+    const MAX_BANDS: usize = 8;
+    const MAX_BAND_WIDTH: u64 = 250_000;
+
+    /// Brackets with increasing separators and rates from 0% to 99%, along with a gross in cents
+    /// that lands in any of the bands or above the top one.
+    fn case() -> impl Strategy<Value = (TaxBrackets, BigUR)> {
+        proptest::collection::vec(1..=MAX_BAND_WIDTH, 0..MAX_BANDS)
+            .prop_flat_map(|widths| {
+                let top: u64 = widths.iter().sum();
+                let rates = proptest::collection::vec(0..100u64, widths.len() + 1);
+                (Just(widths), rates, 0..2 * (top + 1) * 100)
+            })
+            .prop_map(|(widths, rates, gross_cents)| {
+                let separators = widths.iter().scan(0, |top, width| {
+                    *top += width;
+                    Some(BigUint::from(*top))
+                });
+                let rates = rates.into_iter().map(|rate| UR64::new(rate, 100));
+                let gross = BigUR::new(BigUint::from(gross_cents), BigUint::from(100u8));
+                (TaxBrackets::base(separators, rates), gross)
+            })
+    }
+
+    proptest! {
+        /// Failing cases are kept under `proptest-regressions`, and replayed before new ones.
+        #[test]
+        fn gross_comes_back_from_its_net((brackets, gross) in case()) {
+            let round_trip = brackets.calc_gross(&brackets.calc_net(&gross));
+            let cent = BigUR::new(BigUint::one(), BigUint::from(100u8));
+            let error = if round_trip > gross {
+                round_trip.clone() - gross.clone()
+            } else {
+                gross.clone() - round_trip.clone()
+            };
+            prop_assert!(
+                error <= cent,
+                "gross {} came back as {}",
+                ApproxRatio(gross),
+                ApproxRatio(round_trip)
+            );
+        }
+    }
+
+    #[test]
+    fn marital_status_round_trips_through_serde_as_its_parsed_name() {
+        for status in MaritalStatus::ALL {
+            let json = serde_json::to_string(&status).unwrap();
+            let name: String = serde_json::from_str(&json).unwrap();
+            assert_eq!(name.parse::<MaritalStatus>(), Ok(status));
+            assert_eq!(
+                serde_json::from_str::<MaritalStatus>(&json).unwrap(),
+                status
+            );
+        }
+        assert_eq!(
+            serde_json::to_string(&MaritalStatus::HeadOfHousehold).unwrap(),
+            "\"head\""
+        );
+    }
+
+    /// Writes `contents` to a data file named after the test using it.
+    fn data_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("equinc-{}.json", name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn rates_parse_the_same_as_percents_decimals_and_fractions() {
+        let rate = UR64::new(22, 100);
+        for data in [r#""22%""#, r#""0.22""#, "[22, 100]"] {
+            let data: RateData = serde_json::from_str(data).unwrap();
+            assert_eq!(data.to_rate(), Ok(rate), "{:?}", data);
+        }
+    }
+
+    #[test]
+    fn from_file_loads_rates_in_any_form() {
+        let path = data_file(
+            "mixed-rates",
+            r#"{"single": {"separators": [10000, 40000], "rates": ["10%", "0.22", [30, 100]]}}"#,
+        );
+        let system = TaxSystem::from_file(&path, 0).unwrap();
+        let taxes = system.calc_taxes(
+            &BigUR::from_integer(50_000u32.into()),
+            MaritalStatus::Single,
+        );
+        assert_eq!(
+            taxes,
+            BigUR::from_integer((1_000u32 + 6_600 + 3_000).into())
+        );
+    }
+
+    #[test]
+    fn from_file_rejects_rates_of_100_percent() {
+        let path = data_file(
+            "full-rate",
+            r#"{"single": {"separators": [10000], "rates": ["10%", "100%"]}}"#,
+        );
+        let err = TaxSystem::from_file(&path, 0).unwrap_err();
+        assert!(err.contains("Expected rates below 100%"), "{}", err);
+    }
+
+    /// The single brackets with the 2020 rates and the given separators, and `top_rate` above them.
+    fn single_brackets(separators: [u64; 6], top_rate: UR64) -> TaxBrackets {
+        let rates = [10, 12, 22, 24, 32, 35].map(|rate| UR64::new(rate, 100));
+        TaxBrackets::base(
+            separators.iter().copied(),
+            rates.iter().copied().chain(std::iter::once(top_rate)),
+        )
+    }
+
+    #[test]
+    fn diff_reports_a_moved_top_separator_and_a_new_top_rate() {
+        let usa_2020 = single_brackets(
+            [9_875, 40_125, 85_525, 163_300, 207_350, 518_400],
+            UR64::new(37, 100),
+        );
+        // A hypothetical 2021 that only indexes the top band and raises its rate.
+        let usa_2021 = single_brackets(
+            [9_875, 40_125, 85_525, 163_300, 207_350, 523_600],
+            UR64::new(396, 1_000),
+        );
+        let dollars = |amount: u64| BigUR::from_integer(amount.into());
+        assert_eq!(
+            usa_2020.diff(&usa_2021),
+            vec![
+                BracketDiff::RemovedSeparator(dollars(518_400)),
+                BracketDiff::AddedSeparator(dollars(523_600)),
+                BracketDiff::RateChanged {
+                    above: Some(dollars(518_400)),
+                    old: UR64::new(37, 100),
+                    new: UR64::new(35, 100),
+                },
+                BracketDiff::RateChanged {
+                    above: Some(dollars(523_600)),
+                    old: UR64::new(37, 100),
+                    new: UR64::new(396, 1_000),
+                },
+            ]
+        );
+        assert_eq!(
+            usa_2020.diff(&usa_2021)[3].to_string(),
+            "Rate above 523600.00 (approx) changed from 37.00% (approx) to 39.60% (approx)"
+        );
+        assert!(usa_2020.diff(&usa_2020).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected one more rate than the 0 separators, but got 0 rates.")]
+    fn schedule_without_rates_is_rejected_at_construction() {
+        TaxBrackets::base(std::iter::empty::<u64>(), std::iter::empty::<UR64>());
+    }
+
+    #[test]
+    fn from_file_rejects_a_schedule_without_rates() {
+        let path = data_file("no-rates", r#"{"single": {"separators": [], "rates": []}}"#);
+        let err = TaxSystem::from_file(&path, 0).unwrap_err();
+        assert!(err.contains("Expected at least one rate"), "{}", err);
+    }
+
+    #[test]
+    fn personal_exemption_comes_off_the_taxable_income() {
+        let path = data_file(
+            "personal-exemption",
+            r#"{"single": {"separators": [], "rates": ["5%"], "personal_exemption": 4000, "dependent_exemption": 1000}}"#,
+        );
+        let income = BigUR::from_integer(50_000u32.into());
+        let taxes = |dependents| {
+            TaxSystem::from_file(&path, dependents)
+                .unwrap()
+                .calc_taxes(&income, MaritalStatus::Single)
+        };
+        // 5% of the $46,000 left after the exemption, and of $44,000 with two dependents.
+        assert_eq!(taxes(0), BigUR::from_integer(2_300u32.into()));
+        assert_eq!(taxes(2), BigUR::from_integer(2_200u32.into()));
+    }
+
+    #[test]
+    fn post_tax_separators_are_below_the_pre_tax_ones() {
+        let brackets = single_brackets(
+            [9_875, 40_125, 85_525, 163_300, 207_350, 518_400],
+            UR64::new(37, 100),
+        );
+        let post_tax = brackets.post_tax_separators();
+        assert_eq!(post_tax.len(), brackets.separators.len());
+        for (post, pre) in post_tax.iter().zip(brackets.separators.iter()) {
+            assert!(post < pre, "{} is not below {}", post, pre);
+        }
+        // The first band is taxed at 10%, so its net ends at 90% of its gross.
+        assert_eq!(post_tax[0], BigUR::new(88_875u32.into(), 10u8.into()));
+    }
+
+    #[test]
+    fn income_stream_is_taxed_a_year_at_a_time() {
+        // Nothing on the first $10,000 of each year and 20% above it.
+        let system = TaxSystem::new(
+            std::iter::once((
+                MaritalStatus::Single,
+                (vec![10_000u32], vec![UR64::new(0, 1), UR64::new(1, 5)]),
+            ))
+            .collect(),
+        );
+        let dollars = |amount: u32| BigUR::from_integer(amount.into());
+        let incomes = [dollars(5_000), dollars(30_000), dollars(60_000)];
+        let taxes = system.calc_taxes_stream(&incomes, MaritalStatus::Single);
+        assert_eq!(taxes, vec![dollars(0), dollars(4_000), dollars(10_000)]);
+        // Spreading the same $95,000 evenly over the three years would have cost less.
+        let total = taxes.into_iter().fold(BigUR::zero(), |sum, tax| sum + tax);
+        assert_eq!(total, dollars(14_000));
+    }
+
+    #[test]
+    fn flats_above_zero_at_the_bottom_or_decreasing_are_problems() {
+        let mut broken = single_brackets(
+            [9_875, 40_125, 85_525, 163_300, 207_350, 518_400],
+            UR64::new(37, 100),
+        );
+        assert!(broken.problems().is_empty());
+        broken.flats.swap(0, 2);
+        let problems = broken.problems();
+        assert!(problems.contains(&"the bottom band has taxes below it".to_owned()));
+        assert!(problems.contains(&"flats are decreasing".to_owned()));
+    }
+
+    #[test]
+    fn headroom_is_the_distance_to_the_next_separator() {
+        let brackets = single_brackets(
+            [9_875, 40_125, 85_525, 163_300, 207_350, 518_400],
+            UR64::new(37, 100),
+        );
+        let dollars = |amount: u64| BigUR::from_integer(amount.into());
+        // Halfway through the 24% band.
+        assert_eq!(
+            brackets.headroom_in_bracket(&dollars(120_000)),
+            Some(dollars(43_300))
+        );
+        assert_eq!(
+            brackets.headroom_in_bracket(&dollars(163_300)),
+            Some(dollars(0))
+        );
+        assert_eq!(brackets.headroom_in_bracket(&dollars(600_000)), None);
+    }
+
+    #[test]
+    fn brackets_map_round_trips_to_an_equal_system() {
+        let rates = vec![UR64::new(1, 10), UR64::new(1, 5), UR64::new(3, 10)];
+        let system = TaxSystem::new(
+            vec![
+                (
+                    MaritalStatus::Single,
+                    (vec![10_000u32, 50_000], rates.clone()),
+                ),
+                (MaritalStatus::Joint, (vec![20_000u32, 100_000], rates)),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let map = system.to_brackets_map();
+        assert_eq!(map.len(), 2);
+        assert_eq!(TaxSystem::from_brackets_map(map), system);
+        let flat = TaxSystem::flat(UR64::new(15, 1_000));
+        assert_eq!(TaxSystem::from_brackets_map(flat.to_brackets_map()), flat);
+    }
+
+    #[test]
+    fn exemption_phases_out_to_nothing_for_a_high_income() {
+        let path = data_file(
+            "exemption-phaseout",
+            r#"{"single": {"separators": [], "rates": ["10%"], "personal_exemption": 10000,
+                "exemption_phaseout": {"threshold": 100000, "rate": "50%"}}}"#,
+        );
+        let system = TaxSystem::from_file(&path, 0).unwrap();
+        let dollars = |amount: u32| BigUR::from_integer(amount.into());
+        let taxes = |income| system.calc_taxes(&dollars(income), MaritalStatus::Single);
+        assert_eq!(taxes(50_000), dollars(4_000));
+        // Halfway through, half of the exemption is left.
+        assert_eq!(taxes(110_000), dollars(10_500));
+        // It's gone by 120,000, so all of the income is taxed.
+        assert_eq!(taxes(200_000), dollars(20_000));
+    }
+
+    #[test]
+    fn indexing_raises_each_separator_and_lowers_the_taxes_slightly() {
+        let usa_2020 = single_brackets(
+            [9_875, 40_125, 85_525, 163_300, 207_350, 518_400],
+            UR64::new(37, 100),
+        );
+        let factor = UR64::new(102, 100);
+        let indexed = usa_2020.indexed(factor);
+        assert_eq!(indexed.separators.len(), usa_2020.separators.len());
+        for (shifted, base) in indexed.separators.iter().zip(usa_2020.separators.iter()) {
+            assert_eq!(*shifted, base.clone() * cast_ratio::<u64, BigUint>(factor));
+        }
+        assert!(indexed.problems().is_empty());
+        let income = BigUR::from_integer(100_000u32.into());
+        // Down from 18,079.50, as more of the income falls in the lower bands.
+        assert_eq!(
+            indexed.calc_taxes(&income),
+            BigUR::new(1_796_109u32.into(), 100u32.into())
+        );
+        assert_eq!(
+            usa_2020.calc_taxes(&income),
+            BigUR::new(1_807_950u32.into(), 100u32.into())
+        );
+    }
+}
@@ -1,19 +1,29 @@
 use currency::Currency;
+use num::{traits::One, BigUint};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 
-use crate::{brackets::MaritalStatus, loc::Location};
+use crate::{
+    brackets::MaritalStatus,
+    exchange::{parse_exchange_rate, CurrencyCode, RatePair},
+    loc::{ExpenseCategory, HsaCoverage, Location, State},
+    logger::LogFormat,
+    report::ReportFormat,
+    util::{
+        cast_ratio, currency_to_bigur, parse_decimal, parse_rate, BigUR, NumberStyle, RoundingMode,
+        UR64,
+    },
+};
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+/// Serializes to the same names that the parser accepts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum AnalysisMode {
     PostTax,
+    #[default]
     Disposable,
 }
 
-impl Default for AnalysisMode {
-    fn default() -> Self {
-        AnalysisMode::Disposable
-    }
-}
-
 impl std::str::FromStr for AnalysisMode {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -34,13 +44,648 @@ impl std::fmt::Display for AnalysisMode {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum DisplayCurrency {
+    #[default]
+    Home,
+    Target,
+}
+
+impl std::str::FromStr for DisplayCurrency {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "home" => Ok(DisplayCurrency::Home),
+            "target" => Ok(DisplayCurrency::Target),
+            _ => Err(format!("Failed to understand display currency {:?}.", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for DisplayCurrency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisplayCurrency::Home => write!(f, "home"),
+            DisplayCurrency::Target => write!(f, "target"),
+        }
+    }
+}
+
+/// The taxes owed on top of the regular income tax. Nothing extra is included by default.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct TaxComponents {
+    pub fica: bool,
+    pub amt: bool,
+    pub salt: bool,
+}
+
+impl std::str::FromStr for TaxComponents {
+    type Err = String;
+    /// Parses a comma separated list like `fica,amt`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut components = TaxComponents::default();
+        for component in s.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+            match component {
+                "fica" => components.fica = true,
+                "amt" => components.amt = true,
+                "salt" => components.salt = true,
+                _ => {
+                    return Err(format!(
+                        "Failed to understand tax component {:?}.",
+                        component
+                    ))
+                }
+            }
+        }
+        Ok(components)
+    }
+}
+
+impl std::fmt::Display for TaxComponents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<_> = [(self.fica, "fica"), (self.amt, "amt"), (self.salt, "salt")]
+            .iter()
+            .filter(|(included, _)| *included)
+            .map(|(_, name)| *name)
+            .collect();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+impl TaxComponents {
+    /// Also includes everything in `other`, so the standalone flags still work alongside these.
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            fica: self.fica || other.fica,
+            amt: self.amt || other.amt,
+            salt: self.salt || other.salt,
+        }
+    }
+}
+
+/// Accepts a trailing `k` or `m` to multiply by a thousand or a million, e.g. `150k` or `1.2m`.
+pub fn parse_currency(s: &str) -> Result<Currency, String> {
+    let trimmed = s.trim();
+    match trimmed.chars().last() {
+        Some('k') | Some('K') => parse_scaled_currency(&trimmed[..trimmed.len() - 1], 1_000u32),
+        Some('m') | Some('M') => parse_scaled_currency(&trimmed[..trimmed.len() - 1], 1_000_000u32),
+        _ => parse_unscaled_currency(s),
+    }
+}
+
+/// Like `parse_currency`, but for the amounts that can't be negative, which is all of them.
+fn parse_amount(s: &str) -> Result<Currency, String> {
+    let value = parse_currency(s)?;
+    currency_to_bigur(&value).map_err(|e| format!("{} Got {:?}.", e, s))?;
+    Ok(value)
+}
+
+/// The number is read as a plain decimal before being scaled, so `1.234k` is 1,234 rather than the
+/// thousands group the `currency` crate reads `1.234` as.
+fn parse_scaled_currency(s: &str, multiplier: u32) -> Result<Currency, String> {
+    let err = || format!("Could not parse {:?} into a currency.", s);
+    let number_start = s
+        .find(|c: char| c.is_ascii_digit() || c == '.')
+        .ok_or_else(err)?;
+    let (prefix, number) = s.split_at(number_start);
+    let value: BigUR = cast_ratio(parse_decimal(number).ok_or_else(err)?);
+    let cents = (value * BigUint::from(multiplier) * BigUint::from(100u8))
+        .round()
+        .to_integer();
+    let hundred = BigUint::from(100u8);
+    let formatted = format!("{}{}.{:02}", prefix, &cents / &hundred, &cents % &hundred);
+    Currency::from_str(&formatted).map_err(|e| e.to_string())
+}
+
+/// The `currency` crate silently truncates anything past the cents, so those values are rounded
+/// half away from zero to the cent instead, with a warning. Like the `currency` crate, exactly three
+/// digits after the last delimiter are read as a thousands group rather than as a fraction.
+fn parse_unscaled_currency(s: &str) -> Result<Currency, String> {
+    let parsed = Currency::from_str(s).map_err(|e| e.to_string())?;
+    let fract_digits: Vec<_> = match s.rfind(['.', ',']) {
+        Some(idx) => s[idx + 1..].chars().filter(char::is_ascii_digit).collect(),
+        None => vec![],
+    };
+    if fract_digits.len() <= 3 {
+        return Ok(parsed);
+    }
+
+    log::warn!(
+        "{:?} has more precision than cents, so it will be rounded to the nearest cent.",
+        s
+    );
+    if fract_digits[2] < '5' {
+        return Ok(parsed);
+    }
+    let symbol = s
+        .chars()
+        .find(|c| !c.is_ascii_digit() && !matches!(c, '-' | '.' | ','))
+        .map(String::from)
+        .unwrap_or_default();
+    let cent = Currency::from_str(&format!("{}0.01", symbol)).map_err(|e| e.to_string())?;
+    if parsed.sign() == currency_num::bigint::Sign::Minus {
+        Ok(parsed - cent)
+    } else {
+        Ok(parsed + cent)
+    }
+}
+
+/// A rate that is at most 1.
+fn parse_share(s: &str) -> Result<UR64, String> {
+    let share = parse_rate(s)?;
+    if share > UR64::one() {
+        return Err(format!("Expected a share of at most 100%, not {:?}.", s));
+    }
+    Ok(share)
+}
+
+/// A tax rate, which has to leave something of the income untaxed.
+fn parse_tax_rate(s: &str) -> Result<UR64, String> {
+    let rate = parse_rate(s)?;
+    if rate >= UR64::one() {
+        return Err(format!("Expected a tax rate below 100%, not {:?}.", s));
+    }
+    Ok(rate)
+}
+
+fn parse_months(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>() {
+        Ok(months) if (1..=12).contains(&months) => Ok(months),
+        _ => Err(format!("Expected between 1 and 12 months, not {:?}.", s)),
+    }
+}
+
+fn parse_pay_periods(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>() {
+        Ok(periods) if periods >= 1 => Ok(periods),
+        _ => Err(format!("Expected at least 1 pay period, not {:?}.", s)),
+    }
+}
+
+fn parse_registered_city(s: &str) -> Result<(Location, PathBuf), String> {
+    match s.find('=') {
+        Some(idx) => Ok((s[..idx].parse()?, PathBuf::from(&s[idx + 1..]))),
+        None => Err(format!(
+            "Expected a city like \"USA///TX///Dallas=dallas.json\", not {:?}.",
+            s
+        )),
+    }
+}
+
+fn parse_expense_share(s: &str) -> Result<(ExpenseCategory, UR64), String> {
+    match s.find('=') {
+        Some(idx) => Ok((s[..idx].trim().parse()?, parse_rate(&s[idx + 1..])?)),
+        None => Err(format!(
+            "Expected a share like \"housing=40%\", not {:?}.",
+            s
+        )),
+    }
+}
+
+/// Run in place of an analysis, e.g. `equinc check`.
 #[derive(structopt::StructOpt, Debug)]
+pub enum Command {
+    /// Validates all of the built-in tax data, reporting anything that looks wrong.
+    Check,
+    /// Analyzes every scenario in a CSV, printing a CSV with their equivalent incomes and taxes.
+    Batch {
+        /// A CSV with the header `income,status,source,target`, and amounts without commas.
+        #[structopt(long, parse(from_os_str))]
+        input_csv: PathBuf,
+    },
+    /// Runs an analysis shared with `--encode`.
+    Decode {
+        /// The encoded options of the analysis.
+        scenario: String,
+    },
+}
+
+/// `Currency` can't be serialized, so amounts are written the way they're entered, like `$1234.56`.
+/// Its digits are grouped when printed, but the groups are left out to read back unambiguously.
+struct Amount(Currency);
+
+impl Serialize for Amount {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_string().replace(',', ""))
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let amount = String::deserialize(deserializer)?;
+        Currency::from_str(&amount)
+            .map(Amount)
+            .map_err(|e| serde::de::Error::custom(e.to_string()))
+    }
+}
+
+mod amount {
+    use super::*;
+
+    pub fn serialize<S: serde::Serializer>(value: &Currency, s: S) -> Result<S::Ok, S::Error> {
+        Amount(value.clone()).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Currency, D::Error> {
+        Amount::deserialize(d).map(|amount| amount.0)
+    }
+}
+
+mod optional_amount {
+    use super::*;
+
+    pub fn serialize<S: serde::Serializer>(
+        value: &Option<Currency>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.clone().map(Amount).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        d: D,
+    ) -> Result<Option<Currency>, D::Error> {
+        Option::<Amount>::deserialize(d).map(|amount| amount.map(|amount| amount.0))
+    }
+}
+
+mod amounts {
+    use super::*;
+
+    pub fn serialize<S: serde::Serializer>(values: &[Currency], s: S) -> Result<S::Ok, S::Error> {
+        let amounts: Vec<_> = values.iter().cloned().map(Amount).collect();
+        amounts.serialize(s)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<Currency>, D::Error> {
+        let amounts = Vec::<Amount>::deserialize(d)?;
+        Ok(amounts.into_iter().map(|amount| amount.0).collect())
+    }
+}
+
+#[derive(structopt::StructOpt, Debug, PartialEq, Serialize, Deserialize)]
+#[structopt(after_help = "Run `equinc check` to validate the built-in tax data instead.")]
 pub struct Opts {
+    #[structopt(env = "EQUINC_SOURCE")]
     pub source: Location,
+    #[structopt(env = "EQUINC_TARGET")]
     pub target: Location,
+    /// The annual income, which must be 0 with `--hourly-rate`.
+    #[structopt(env = "EQUINC_INCOME", parse(try_from_str = parse_amount))]
+    #[serde(with = "amount")]
     pub income: Currency,
+    /// The monthly expenses, which must be 0 with `--expenses-percent`.
+    #[structopt(env = "EQUINC_MONTHLY_EXPENSES", parse(try_from_str = parse_amount))]
+    #[serde(with = "amount")]
     pub monthly_expenses: Currency,
+    #[structopt(env = "EQUINC_STATUS")]
     pub status: MaritalStatus,
     #[structopt(default_value, long)]
     pub usage: AnalysisMode,
+    /// What the income and expenses are paid in, like `USD`. Otherwise it's the currency of the
+    /// symbol they're entered with, if any, or else the home currency.
+    #[structopt(long)]
+    pub currency: Option<CurrencyCode>,
+    /// A JSON file mapping pairs like `"USD/GBP"` to rates like `"0.75"`, replacing the built-in
+    /// rates.
+    #[structopt(long, parse(from_os_str))]
+    pub exchange_rate_file: Option<PathBuf>,
+    /// A single rate like `USD/GBP=0.75`, taking precedence over the file and built-in rates.
+    #[structopt(long, parse(try_from_str = parse_exchange_rate))]
+    pub exchange_rate: Option<(RatePair, UR64)>,
+    /// Whether to print the results in the home (or `--currency`) or target location's currency.
+    #[structopt(default_value, long)]
+    pub display_currency: DisplayCurrency,
+    /// The taxes to include on top of the income tax, like `fica,amt`. Combines with the
+    /// standalone `--fica`, `--amt` and `--salt`.
+    #[structopt(long)]
+    pub include: Option<TaxComponents>,
+    /// Pay the Alternative Minimum Tax instead of the regular tax whenever it is higher.
+    #[structopt(long)]
+    pub amt: bool,
+
+    /// Include payroll taxes like FICA, with the Additional Medicare Tax for high earners.
+    #[structopt(long)]
+    pub fica: bool,
+
+    /// Charge a flat social contribution on top of the income tax, in the countries that model one.
+    /// The UK's is a hypothetical 2% on all income.
+    #[structopt(long)]
+    pub social_contribution: bool,
+
+    /// Leave out city taxes, even where they're known.
+    #[structopt(long)]
+    pub ignore_city_tax: bool,
+    /// The state worked in at the target, when it's not the one lived in. It taxes the income as
+    /// well, unless the two have a reciprocity agreement.
+    #[structopt(long)]
+    pub work_state: Option<State>,
+
+    /// Deduct state and local taxes from the federal taxable income, up to the SALT cap.
+    #[structopt(long)]
+    pub salt: bool,
+    /// Take the 2020 standard deductions off the income, the country's before its brackets and
+    /// the state's before its own. Where a jurisdiction has none, its brackets are left as they are.
+    #[structopt(long)]
+    pub standard_deduction: bool,
+    /// Scale every bracket's separators by this inflation factor, like `1.02`, to approximate a
+    /// later year than the built-in ones are from.
+    #[structopt(long, parse(try_from_str = parse_rate))]
+    pub bracket_index: Option<UR64>,
+    /// The income is already taxable, net of any deductions, so none are taken off before the
+    /// brackets. Rules out the SALT deduction, the standard deductions and the pre-tax HSA
+    /// contribution.
+    #[structopt(
+        long,
+        conflicts_with_all = &["salt", "standard-deduction", "hsa-contribution"]
+    )]
+    pub income_is_taxable: bool,
+    /// Also report the taxes owed to both countries after a treaty credit.
+    #[structopt(long)]
+    pub treaty_credit: bool,
+    /// The number of paychecks the take-home income at the target is split into.
+    #[structopt(default_value = "26", long, parse(try_from_str = parse_pay_periods))]
+    pub pay_periods: u32,
+    /// An hourly wage to use in place of the income, annualized with the hours and weeks worked.
+    #[structopt(long, parse(try_from_str = parse_amount))]
+    #[serde(with = "optional_amount")]
+    pub hourly_rate: Option<Currency>,
+    /// The hours worked a week with `--hourly-rate`, 40 by default.
+    #[structopt(long, requires = "hourly-rate")]
+    pub hours_per_week: Option<u32>,
+    /// The weeks worked a year with `--hourly-rate`, 52 by default.
+    #[structopt(long, requires = "hourly-rate")]
+    pub weeks_per_year: Option<u32>,
+    /// The hours worked a year, in place of the hours a week and weeks a year. Also works without
+    /// `--hourly-rate`, to show the take-home per hour.
+    #[structopt(long, conflicts_with_all = &["hours-per-week", "weeks-per-year"])]
+    pub hours_per_year: Option<u32>,
+    /// Treat the income as earned over this many months, so it's annualized for the brackets. The
+    /// report shows full-year figures, plus the taxes prorated back to the months worked.
+    #[structopt(long, parse(try_from_str = parse_months))]
+    pub months_worked: Option<u32>,
+    /// Also show the taxes for moving partway through the year, after this share of it (like `50%`)
+    /// was spent at home. Only works within a country.
+    #[structopt(long, parse(try_from_str = parse_share))]
+    pub split_year: Option<UR64>,
+    /// Also split the taxes at the target into quarterly estimated payments.
+    #[structopt(long)]
+    pub quarterly: bool,
+    /// A bonus on top of the income, which also reports what's withheld from it at the flat
+    /// supplemental rate against what's actually owed on it.
+    #[structopt(long, parse(try_from_str = parse_amount))]
+    #[serde(with = "optional_amount")]
+    pub bonus: Option<Currency>,
+    /// An employer's retirement match, which counts towards the total compensation but isn't taxed
+    /// now.
+    #[structopt(long, parse(try_from_str = parse_amount))]
+    #[serde(with = "optional_amount")]
+    pub employer_match: Option<Currency>,
+    /// Also sum the taxes at home and at the target over this many years, with the income growing
+    /// by `--income-growth` each year.
+    #[structopt(long, requires = "income-growth")]
+    pub career_years: Option<u32>,
+    /// How much the income grows each year of `--career-years`, like `5%`.
+    #[structopt(long, parse(try_from_str = parse_rate), requires = "career-years")]
+    pub income_growth: Option<UR64>,
+    /// Also show the equivalent income deflated to today's money, as of this future year.
+    #[structopt(long, requires = "inflation-rate")]
+    pub project_to: Option<i32>,
+    /// The yearly inflation, like `2%`, expected until `--project-to`.
+    #[structopt(long, parse(try_from_str = parse_rate), requires = "project-to")]
+    pub inflation_rate: Option<UR64>,
+    /// A spouse's income, which is combined with the entered income when filing jointly.
+    #[structopt(long, parse(try_from_str = parse_amount))]
+    #[serde(with = "optional_amount")]
+    pub spouse_income: Option<Currency>,
+    /// Income that is earned but never taxed, such as municipal bond interest.
+    #[structopt(long, parse(try_from_str = parse_amount))]
+    #[serde(with = "optional_amount")]
+    pub tax_exempt: Option<Currency>,
+    /// Put in a health savings account out of the income each year, which isn't taxed up to the
+    /// limit for the `--hsa-coverage`.
+    #[structopt(long, parse(try_from_str = parse_amount))]
+    #[serde(with = "optional_amount")]
+    pub hsa_contribution: Option<Currency>,
+    /// The part of the income from a pass-through business, which gets the 20% deduction on
+    /// qualified business income where there's one.
+    #[structopt(long, parse(try_from_str = parse_amount))]
+    #[serde(with = "optional_amount")]
+    pub qbi_income: Option<Currency>,
+    /// Who the health savings account covers, either `self` or `family`.
+    #[structopt(default_value, long)]
+    pub hsa_coverage: HsaCoverage,
+    /// Spent on the care of dependents over the year, which is partly credited against the taxes
+    /// where there's a credit for it. How much qualifies depends on the `--dependents`.
+    #[structopt(long, parse(try_from_str = parse_amount))]
+    #[serde(with = "optional_amount")]
+    pub childcare_expenses: Option<Currency>,
+    /// Capital gains realized over the year, taxed only where there's a schedule for them.
+    #[structopt(long, parse(try_from_str = parse_amount))]
+    #[serde(with = "optional_amount")]
+    pub capital_gains: Option<Currency>,
+    /// Capital losses carried forward from earlier years, which offset the capital gains. Whatever
+    /// isn't used up is reported as carried into the next year.
+    #[structopt(long, parse(try_from_str = parse_amount))]
+    #[serde(with = "optional_amount")]
+    pub loss_carryforward: Option<Currency>,
+    /// Locations to compare against to find the one with the lowest taxes.
+    #[structopt(long = "candidate")]
+    pub candidates: Vec<Location>,
+    /// A JSON file of extra brackets at the target, mapping each status to its `separators` and
+    /// `rates`. Rates can be written as `"10%"`, `"0.10"`, or `[10, 100]`. Each status can also
+    /// have a `personal_exemption` and a `dependent_exemption`, which shrink by the `rate` for every
+    /// unit above the `threshold` of an `exemption_phaseout`.
+    #[structopt(long, parse(from_os_str))]
+    pub extra_tax_file: Option<PathBuf>,
+    /// Dependents claimed, for tax files with a `dependent_exemption` and for the qualifying
+    /// `--childcare-expenses`.
+    #[structopt(default_value = "0", long)]
+    pub dependents: u32,
+    /// A flat rate at the target on top of the known taxes, such as `1%` or `0.01`.
+    #[structopt(long, parse(try_from_str = parse_tax_rate))]
+    pub extra_flat_tax: Option<UR64>,
+    /// A city without built-in data and a JSON file of its taxes, like `USA///TX///Dallas=dallas.json`,
+    /// using the same layout as `--extra-tax-file`. Repeatable, and replaces any built-in data.
+    #[structopt(long = "register-city", parse(try_from_str = parse_registered_city))]
+    pub registered_cities: Vec<(Location, PathBuf)>,
+    /// Only print how the target's schedule differs from the one in this JSON file, which uses
+    /// the same layout as `--extra-tax-file`.
+    #[structopt(long, parse(from_os_str))]
+    pub diff_tax_file: Option<PathBuf>,
+    /// How the report is printed: `text`, `markdown` or `columns`, which puts home and target side
+    /// by side.
+    #[structopt(default_value, long)]
+    pub format: ReportFormat,
+    /// How amounts are rounded to the cent in the report: truncate, half_up or half_even.
+    #[structopt(default_value, long)]
+    pub rounding: RoundingMode,
+    /// Fail instead of rounding when any figure in the report isn't exact to the cent.
+    #[structopt(long)]
+    pub precision_exact: bool,
+    /// How amounts are separated in the report: us (518,400.00), german (518.400,00) or plain.
+    #[structopt(default_value, long)]
+    pub number_style: NumberStyle,
+    /// Budget the monthly expenses as a share of the monthly take-home at home, like `50%`.
+    #[structopt(long, parse(try_from_str = parse_share))]
+    pub expenses_percent: Option<UR64>,
+    /// Keep the expenses the same at the target rather than scaling them by the cost of living.
+    #[structopt(long)]
+    pub no_col_adjust: bool,
+    /// Cap expenses at the net income instead of stopping when they exceed it.
+    #[structopt(long)]
+    pub clamp_expenses: bool,
+    /// Share of the expenses spent on a category, like `housing=40%`. Repeatable, and whatever is
+    /// left over scales with the blended cost of living.
+    #[structopt(long = "expense-share", parse(try_from_str = parse_expense_share))]
+    pub expense_shares: Vec<(ExpenseCategory, UR64)>,
+    /// Also report how much more income fits in the current band at the target.
+    #[structopt(long)]
+    pub bracket_headroom: bool,
+    /// Also report how much of the next dollar earned at the target is kept after taxes.
+    #[structopt(long)]
+    pub next_dollar: bool,
+    /// Whether the log is written as `text` or as `json` lines.
+    #[structopt(default_value, long)]
+    pub log_format: LogFormat,
+    /// Print just the equivalent income, formatted and raw, on a single line.
+    #[structopt(long)]
+    pub summary_only: bool,
+    /// Fail when a tax at home or at the target has no brackets for the status, instead of
+    /// warning and leaving it untaxed.
+    #[structopt(long)]
+    pub strict_statuses: bool,
+    /// Before the report, print the brackets of every tax at the target for the status, and then
+    /// what they merge into.
+    #[structopt(long)]
+    pub dump_merged: bool,
+    /// Only print the brackets of every tax at the target merged together, for the status.
+    #[structopt(long)]
+    pub show_schedule: bool,
+    /// Only print the other options encoded into a string that `equinc decode` runs again,
+    /// including the ones taken from the environment.
+    #[structopt(long)]
+    pub encode: bool,
+    /// Only print the gross income needed at the target to have this much disposable income over a
+    /// year, once the expenses (scaled to the target) are paid.
+    #[structopt(long, parse(try_from_str = parse_amount))]
+    #[serde(with = "optional_amount")]
+    pub disposable_goal: Option<Currency>,
+    /// Only print the taxes at the target on each of these annual incomes, like vesting RSUs, and
+    /// their total. Repeatable, one per year.
+    #[structopt(long = "stream-income", parse(try_from_str = parse_amount))]
+    #[serde(with = "amounts")]
+    pub income_stream: Vec<Currency>,
+    /// Only print the effective tax rate at the target at each of these incomes, in order, to
+    /// trace how it changes with the income. Repeatable.
+    #[structopt(long = "curve-income", parse(try_from_str = parse_amount))]
+    #[serde(with = "amounts")]
+    pub rate_curve: Vec<Currency>,
+    /// Only print the taxes at the target under every filing status, from lowest to highest.
+    #[structopt(long)]
+    pub compare_statuses: bool,
+    /// Only print the taxes in every supported state, from lowest to highest. Cities are left out,
+    /// and so is everything only added at the target, like `--extra-tax-file`.
+    #[structopt(long)]
+    pub all_states: bool,
+    /// Only print how much the target costs to live in relative to the home.
+    #[structopt(long)]
+    pub cost_factor_only: bool,
+    /// An actual offer at the target, in the display currency, to report whether it beats the
+    /// equivalent income and by how much.
+    #[structopt(long, parse(try_from_str = parse_amount))]
+    #[serde(with = "optional_amount")]
+    pub offer: Option<Currency>,
+    /// Exit with status 2 if the equivalent income, in the display currency, is above this.
+    #[structopt(long, parse(try_from_str = parse_amount))]
+    #[serde(with = "optional_amount")]
+    pub fail_if_above: Option<Currency>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(s: &str) -> BigUR {
+        currency_to_bigur(&parse_currency(s).unwrap()).unwrap()
+    }
+
+    fn cents(amount: u32) -> BigUR {
+        BigUR::new(amount.into(), 100u32.into())
+    }
+
+    fn dollars(amount: u32) -> BigUR {
+        BigUR::from_integer(amount.into())
+    }
+
+    #[test]
+    fn sub_cent_amounts_are_rounded_to_the_nearest_cent() {
+        assert_eq!(parsed("$1.23"), cents(123));
+        assert_eq!(parsed("$1.2345"), cents(123));
+        assert_eq!(parsed("$1.2351"), cents(124));
+        assert_eq!(parsed("$1.2399"), cents(124));
+    }
+
+    #[test]
+    fn incomes_take_thousand_and_million_suffixes() {
+        assert_eq!(parsed("150k"), dollars(150_000));
+        assert_eq!(parsed("$150K"), dollars(150_000));
+        assert_eq!(parsed("1.2m"), dollars(1_200_000));
+        assert_eq!(parsed("150000"), dollars(150_000));
+    }
+
+    #[test]
+    fn analysis_mode_round_trips_through_serde_as_its_parsed_name() {
+        for (mode, name) in [
+            (AnalysisMode::PostTax, "post_tax"),
+            (AnalysisMode::Disposable, "disposable"),
+        ] {
+            let json = serde_json::to_string(&mode).unwrap();
+            assert_eq!(json, format!("{:?}", name));
+            assert_eq!(serde_json::from_str::<AnalysisMode>(&json).unwrap(), mode);
+            assert_eq!(name.parse::<AnalysisMode>(), Ok(mode));
+        }
+    }
+
+    #[test]
+    fn tax_components_parse_from_a_list_and_default_to_none() {
+        let components: TaxComponents = "fica, amt".parse().unwrap();
+        assert_eq!(
+            components,
+            TaxComponents {
+                fica: true,
+                amt: true,
+                salt: false,
+            }
+        );
+        assert_eq!(components.to_string(), "fica,amt");
+        assert_eq!("".parse(), Ok(TaxComponents::default()));
+        assert!("fica,vat".parse::<TaxComponents>().is_err());
+    }
+
+    #[test]
+    fn pay_periods_must_be_at_least_one() {
+        assert_eq!(parse_pay_periods("1"), Ok(1));
+        assert_eq!(parse_pay_periods("26"), Ok(26));
+        assert!(parse_pay_periods("0").is_err());
+        assert!(parse_pay_periods("-1").is_err());
+    }
+
+    #[test]
+    fn amounts_must_not_be_negative() {
+        assert!(parse_amount("5k").is_ok());
+        assert!(parse_amount("$0").is_ok());
+        for negative in ["-5k", "-$5,000", "-0.01"] {
+            assert!(parse_currency(negative).is_ok());
+            assert!(parse_amount(negative).is_err(), "{}", negative);
+        }
+    }
+
+    #[test]
+    fn tax_rates_must_be_below_one_hundred_percent() {
+        assert_eq!(parse_tax_rate("1%"), Ok(UR64::new(1, 100)));
+        assert_eq!(parse_tax_rate("99.9%"), Ok(UR64::new(999, 1_000)));
+        assert!(parse_tax_rate("100%").is_err());
+        assert!(parse_tax_rate("200%").is_err());
+        assert!(parse_tax_rate("1").is_err());
+    }
 }
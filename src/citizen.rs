@@ -1,47 +1,913 @@
-use num::BigUint;
+use num::{
+    traits::{One, Zero},
+    BigUint,
+};
 
 use crate::brackets::MaritalStatus;
 use crate::cfg::AnalysisMode;
-use crate::loc::Location;
-use crate::util::{ApproxRatio, BigUR};
+use crate::exchange::{convert, CurrencyCode};
+use crate::loc::{ExpenseCategory, HsaCoverage, Location};
+use crate::util::{cast_ratio, ApproxRatio, BigUR, Money, UR64};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Citizen {
     // TODO consider specific currencies
-    pub income: BigUR,
-    pub monthly_expenses: BigUR,
+    pub income: Money,
+    /// Income like municipal bond interest, which is earned but never taxed.
+    pub tax_exempt_income: Money,
+    pub monthly_expenses: Money,
+    /// Gains realized over the year, which are only taxed by their own schedules and don't count
+    /// toward the income.
+    pub capital_gains: Money,
+    /// Capital losses from earlier years, which offset the gains before they're taxed.
+    pub loss_carryforward: Money,
+    /// Put in a health savings account out of the income, before taxes up to the limit.
+    pub hsa_contribution: Money,
+    pub hsa_coverage: HsaCoverage,
+    /// The part of the income from a pass-through business, which is partly deducted where
+    /// there's a deduction for it.
+    pub qbi_income: Money,
+    /// Spent over the year on the care of dependents, which earns a credit where there's one.
+    pub childcare_expenses: Money,
+    /// The dependents in care, which sets how much of the childcare expenses qualify.
+    pub care_dependents: u32,
     pub status: MaritalStatus,
     pub home: Location,
+    /// What the income and expenses are paid in, and so what amounts are given in. Usually the
+    /// home currency, but not for expats paid in a foreign one.
+    pub currency: CurrencyCode,
+    /// Whether the Alternative Minimum Tax should be considered.
+    pub amt: bool,
+    /// Whether state and local taxes are deducted from the federal taxable income.
+    pub salt: bool,
+    /// The shares of the expenses that scale with their own category's cost of living.
+    pub expense_shares: Vec<(ExpenseCategory, UR64)>,
+    /// Whether expenses scale with the cost of living at all. The sales tax still applies.
+    pub adjust_cost_of_living: bool,
+    /// Whether expenses above the net income are capped at it rather than rejected.
+    pub clamp_expenses: bool,
 }
 
 impl Citizen {
+    /// Someone earning `income` at `home`, paid in its currency, with nothing else to their
+    /// finances and expenses that follow the cost of living.
+    pub fn new(income: Money, status: MaritalStatus, home: Location) -> Self {
+        Self {
+            income,
+            tax_exempt_income: Money::zero(),
+            monthly_expenses: Money::zero(),
+            capital_gains: Money::zero(),
+            loss_carryforward: Money::zero(),
+            hsa_contribution: Money::zero(),
+            hsa_coverage: HsaCoverage::default(),
+            qbi_income: Money::zero(),
+            childcare_expenses: Money::zero(),
+            care_dependents: 0,
+            status,
+            currency: home.currency(),
+            home,
+            amt: false,
+            salt: false,
+            expense_shares: vec![],
+            adjust_cost_of_living: true,
+            clamp_expenses: false,
+        }
+    }
+
+    /// The same citizen earning `income` instead, with everything else kept.
+    pub fn with_income(mut self, income: BigUR) -> Self {
+        self.income = Money(income);
+        self
+    }
+
+    /// The same citizen budgeting `share` of their take-home at home for expenses.
+    pub fn with_expenses_share_of_net(mut self, share: UR64) -> Self {
+        let monthly_net = (self.calc_net_at(&self.home) + self.tax_exempt_income.0.clone())
+            / BigUR::from_integer(BigUint::from(12u8));
+        self.monthly_expenses = Money(monthly_net * cast_ratio(share));
+        self
+    }
+
+    pub fn total_income(&self) -> BigUR {
+        (self.income.clone() + self.tax_exempt_income.clone()).0
+    }
+
     pub fn calc_taxes(&self) -> BigUR {
-        self.home.calc_taxes(&self.income, self.status)
+        self.calc_taxes_at(&self.home)
     }
 
+    /// The taxes owed at `loc`, given in the citizen's currency.
     pub fn calc_taxes_at(&self, loc: &Location) -> BigUR {
-        loc.calc_taxes(&self.income, self.status)
+        self.calc_taxes_filing_as(loc, self.status)
+    }
+
+    /// The part of the HSA contribution that isn't taxed at `loc`, given in its currency.
+    fn hsa_pre_tax_amount_at(&self, loc: &Location) -> BigUR {
+        let contribution = convert(&self.hsa_contribution.0, self.currency, loc.currency());
+        loc.hsa_pre_tax_amount(&contribution, self.hsa_coverage)
+    }
+
+    /// The deduction at `loc` on the qualified business income, when the rest of the taxable
+    /// income is `income`. Given in its currency.
+    fn qbi_deduction_at(&self, loc: &Location, income: &BigUR, status: MaritalStatus) -> BigUR {
+        let qbi = convert(&self.qbi_income.0, self.currency, loc.currency());
+        loc.qbi_deduction(&qbi, income, status)
+    }
+
+    /// `income` at `loc` with the pre-tax HSA contribution and the QBI deduction taken out, given
+    /// in its currency.
+    fn taxable_income_at(&self, loc: &Location, income: &BigUR, status: MaritalStatus) -> BigUR {
+        let income = convert(income, self.currency, loc.currency());
+        let pre_tax = self.hsa_pre_tax_amount_at(loc);
+        let income = if income > pre_tax {
+            income - pre_tax
+        } else {
+            BigUR::zero()
+        };
+        let deduction = self.qbi_deduction_at(loc, &income, status);
+        income - deduction
+    }
+
+    /// The dependent-care credit at `loc`, given in its currency.
+    fn dependent_care_credit_at(&self, loc: &Location) -> BigUR {
+        let expenses = convert(&self.childcare_expenses.0, self.currency, loc.currency());
+        loc.dependent_care_credit(&expenses, self.care_dependents)
+    }
+
+    /// The taxes owed at `loc` if filing as `status` instead, given in the citizen's currency.
+    pub fn calc_taxes_filing_as(&self, loc: &Location, status: MaritalStatus) -> BigUR {
+        self.calc_taxes_of_at(loc, &self.income.0, status)
+    }
+
+    /// The taxes on earning `income` at `loc` before the dependent-care credit, given in its
+    /// currency. The AMT adds the pre-tax HSA contribution and the QBI deduction back, so it's
+    /// taken on all of `income`.
+    fn calc_taxes_before_credit_at(
+        &self,
+        loc: &Location,
+        income: &BigUR,
+        status: MaritalStatus,
+    ) -> BigUR {
+        let taxable = self.taxable_income_at(loc, income, status);
+        let amt_income = self.amt_income_at(loc, income);
+        loc.calc_taxes_with_amt_income(&taxable, amt_income.as_ref(), status, self.salt)
+    }
+
+    /// The income the AMT is taken on, in the currency of `loc`, if the AMT applies.
+    fn amt_income_at(&self, loc: &Location, income: &BigUR) -> Option<BigUR> {
+        Some(convert(income, self.currency, loc.currency())).filter(|_| self.amt)
+    }
+
+    /// The taxes owed on earning `income` at `loc`, with both given in the citizen's currency.
+    fn calc_taxes_of_at(&self, loc: &Location, income: &BigUR, status: MaritalStatus) -> BigUR {
+        let taxes = self.calc_taxes_before_credit_at(loc, income, status);
+        let credit = std::cmp::min(taxes.clone(), self.dependent_care_credit_at(loc));
+        convert(&(taxes - credit), loc.currency(), self.currency)
+    }
+
+    /// The taxes owed at `loc` over `years`, with the income growing by `growth` after each one.
+    /// The brackets stay as they are. Given in the citizen's currency.
+    pub fn calc_career_taxes_at(&self, loc: &Location, growth: UR64, years: u32) -> BigUR {
+        let growth: BigUR = cast_ratio(UR64::one() + growth);
+        let mut income = self.income.0.clone();
+        let mut total = BigUR::zero();
+        for _ in 0..years {
+            total += self.calc_taxes_of_at(loc, &income, self.status);
+            income *= growth.clone();
+        }
+        total
+    }
+
+    /// The share of each of `incomes` that goes to taxes at `loc`, paired with the income, for
+    /// tracing how progressive the taxes are. Incomes are given in the citizen's currency, and
+    /// everything else about the citizen stays the same.
+    pub fn effective_rate_curve_at(
+        &self,
+        loc: &Location,
+        incomes: &[BigUR],
+    ) -> Vec<(BigUR, BigUR)> {
+        incomes
+            .iter()
+            .map(|income| {
+                let rate = if income.is_zero() {
+                    BigUR::zero()
+                } else {
+                    self.calc_taxes_of_at(loc, income, self.status) / income.clone()
+                };
+                (income.clone(), rate)
+            })
+            .collect()
+    }
+
+    /// The taxes owed at `loc` on the top `amount` of the income, like a bonus, which is at the
+    /// marginal rates. Given in the citizen's currency, and `amount` must be part of the income.
+    pub fn calc_taxes_on_top_at(&self, loc: &Location, amount: &BigUR) -> BigUR {
+        let without = self.income.0.clone() - amount.clone();
+        self.calc_taxes_at(loc) - self.calc_taxes_of_at(loc, &without, self.status)
+    }
+
+    /// The take-home income at `loc`, given in the citizen's currency. The tax exempt income is
+    /// left out.
+    pub fn calc_net_at(&self, loc: &Location) -> BigUR {
+        self.calc_net_of_at(loc, &self.income.0)
+    }
+
+    /// The take-home from earning `income` at `loc`, with both given in the citizen's currency.
+    /// The pre-tax HSA contribution still belongs to the citizen, so it counts, and so does the
+    /// dependent-care credit.
+    fn calc_net_of_at(&self, loc: &Location, income: &BigUR) -> BigUR {
+        let taxable = self.taxable_income_at(loc, income, self.status);
+        let pre_tax = convert(income, self.currency, loc.currency()) - taxable.clone();
+        let amt_income = self.amt_income_at(loc, income);
+        let net =
+            loc.calc_net_with_amt_income(&taxable, amt_income.as_ref(), self.status, self.salt);
+        let credit = std::cmp::min(taxable - net.clone(), self.dependent_care_credit_at(loc));
+        convert(&(net + pre_tax + credit), loc.currency(), self.currency)
+    }
+
+    /// The gains left after the carried forward losses offset them.
+    fn taxable_capital_gains(&self) -> BigUR {
+        if self.capital_gains > self.loss_carryforward {
+            (self.capital_gains.clone() - self.loss_carryforward.clone()).0
+        } else {
+            BigUR::zero()
+        }
+    }
+
+    /// The losses left to carry into the next year once this year's gains are offset.
+    pub fn remaining_loss_carryforward(&self) -> BigUR {
+        if self.loss_carryforward > self.capital_gains {
+            (self.loss_carryforward.clone() - self.capital_gains.clone()).0
+        } else {
+            BigUR::zero()
+        }
+    }
+
+    /// The taxes on the capital gains at `loc`, given in the citizen's currency.
+    pub fn calc_capital_gains_taxes_at(&self, loc: &Location) -> BigUR {
+        let gains = convert(&self.taxable_capital_gains(), self.currency, loc.currency());
+        let taxes = loc.calc_capital_gains_taxes(&gains, self.status);
+        convert(&taxes, loc.currency(), self.currency)
     }
 
-    pub fn estimate_equivalent_income_at(&self, target: &Location, mode: AnalysisMode) -> BigUR {
-        let net = self.home.calc_net(&self.income, self.status);
+    /// How much more income fits in the current band of the merged brackets at `loc`, given in the
+    /// citizen's currency. `None` in the top band, or where there are no taxes.
+    pub fn bracket_headroom_at(&self, loc: &Location) -> Option<BigUR> {
+        let income = self.taxable_income_at(loc, &self.income.0, self.status);
+        let headroom = loc
+            .tax_system()?
+            .brackets(self.status)?
+            .headroom_in_bracket(&income)?;
+        Some(convert(&headroom, loc.currency(), self.currency))
+    }
+
+    /// The share of the next unit earned at `loc` that's kept after the merged brackets, which
+    /// include the payroll taxes when they're on. The AMT and the SALT deduction are left out.
+    pub fn next_unit_kept_share_at(&self, loc: &Location) -> UR64 {
+        let income = self.taxable_income_at(loc, &self.income.0, self.status);
+        let marginal_rate = loc
+            .tax_system()
+            .and_then(|system| Some(system.brackets(self.status)?.marginal_rate(&income)))
+            .unwrap_or_else(UR64::zero);
+        UR64::one() - marginal_rate
+    }
+
+    /// Panics if there are no candidates.
+    pub fn cheapest_tax_location<'a>(&self, candidates: &'a [Location]) -> &'a Location {
+        candidates
+            .iter()
+            .min_by_key(|loc| self.calc_taxes_at(loc))
+            .expect("there to be at least one candidate location.")
+    }
+
+    /// Under a simplified tax treaty, the smaller of the home and foreign taxes is credited against
+    /// the larger.
+    pub fn calc_treaty_credit(&self, foreign: &Location) -> BigUR {
+        std::cmp::min(self.calc_taxes(), self.calc_taxes_at(foreign))
+    }
+
+    /// The taxes owed when both home and `foreign` tax the income, after the treaty credit.
+    pub fn calc_taxes_with_treaty_credit(&self, foreign: &Location) -> BigUR {
+        self.calc_taxes() + self.calc_taxes_at(foreign) - self.calc_treaty_credit(foreign)
+    }
+
+    /// The taxes when moving to `target` after `home_share` of the year, which must be at most 1.
+    /// Each location's taxes on the full-year income, with everything configured for it, are
+    /// prorated to the part of the year spent there. Since the country is shared, so is its tax.
+    pub fn calc_split_year_taxes(&self, target: &Location, home_share: UR64) -> BigUR {
+        assert!(
+            self.home.country == target.country,
+            "Split-year taxes need the home and target to share a country."
+        );
+        let home_share: BigUR = cast_ratio(home_share);
+        let target_share = BigUR::one() - home_share.clone();
+        self.calc_taxes() * home_share + self.calc_taxes_at(target) * target_share
+    }
+
+    /// Finds the gross income at `loc` that nets `net`, with both given in the citizen's currency.
+    fn calc_gross_at(&self, loc: &Location, net: &BigUR) -> BigUR {
+        let gross = self.calc_gross_without_amt_add_backs_at(loc, net);
+        if !self.amt || self.calc_net_of_at(loc, &gross) >= *net {
+            return gross;
+        }
+        // The AMT won on the added back income, which only raises the taxes, so the gross is
+        // above the one found. It's bisected to the cent from there.
+        let cent = BigUR::new(BigUint::one(), BigUint::from(100u8));
+        let two = BigUR::from_integer(BigUint::from(2u8));
+        let mut low = gross.clone();
+        let mut high = gross.clone() * two.clone();
+        while self.calc_net_of_at(loc, &high) < *net {
+            low = high.clone();
+            high *= two.clone();
+        }
+        while high.clone() - low.clone() > cent {
+            let mid = (low.clone() + high.clone()) / two.clone();
+            if self.calc_net_of_at(loc, &mid) < *net {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        high
+    }
+
+    /// Like `calc_gross_at`, but with the AMT taken on the taxable income, which is exact unless
+    /// the AMT wins on the pre-tax HSA contribution or the QBI deduction being added back.
+    fn calc_gross_without_amt_add_backs_at(&self, loc: &Location, net: &BigUR) -> BigUR {
+        let net = convert(net, self.currency, loc.currency());
+        // The pre-tax part of the contribution passes straight through, untaxed.
+        let pre_tax = std::cmp::min(net.clone(), self.hsa_pre_tax_amount_at(loc));
+        let taxed_net = net - pre_tax.clone();
+        // The QBI deduction depends on the gross, so it's taken from a first estimate without it.
+        // That's exact unless the deduction is phasing out or limited by the income.
+        let estimate = self.calc_gross_of_taxed_net_at(loc, &taxed_net);
+        let deduction = std::cmp::min(
+            taxed_net.clone(),
+            self.qbi_deduction_at(loc, &estimate, self.status),
+        );
+        let gross =
+            self.calc_gross_of_taxed_net_at(loc, &(taxed_net - deduction.clone())) + deduction;
+        convert(&(gross + pre_tax), loc.currency(), self.currency)
+    }
+
+    /// Finds the taxable income at `loc` that nets `taxed_net`, with both given in its currency.
+    fn calc_gross_of_taxed_net_at(&self, loc: &Location, taxed_net: &BigUR) -> BigUR {
+        // The whole credit is used up unless the taxes are below it, in which case they're wiped
+        // out and the gross is the net.
+        let credit = self.dependent_care_credit_at(loc);
+        if *taxed_net > credit {
+            let credited = taxed_net.clone() - credit.clone();
+            let gross = loc.calc_gross(&credited, self.status, self.amt, self.salt);
+            if loc.calc_taxes(&gross, self.status, self.amt, self.salt) >= credit {
+                return gross;
+            }
+        }
+        taxed_net.clone()
+    }
+
+    /// How much more the expenses cost at `target`, with each category's share scaled by its own
+    /// factor and the rest by the blended one.
+    fn living_costs_ratio(&self, target: &Location) -> Result<BigUR, String> {
+        if !self.adjust_cost_of_living {
+            return Ok(BigUR::one());
+        }
+        let categorized: UR64 = self.expense_shares.iter().map(|(_, share)| *share).sum();
+        if categorized > UR64::one() {
+            return Err("Expense shares add up to more than 100%.".to_owned());
+        }
+        let blended = target.get_living_costs_factor()? / self.home.get_living_costs_factor()?;
+        let rest: BigUR = cast_ratio(UR64::one() - categorized);
+        self.expense_shares
+            .iter()
+            .map(|(category, share)| {
+                let share: BigUR = cast_ratio(*share);
+                Ok(share * target.get_category_living_costs_factor(*category)?
+                    / self.home.get_category_living_costs_factor(*category)?)
+            })
+            .try_fold(rest * blended, |total, part: Result<BigUR, String>| {
+                Ok(total + part?)
+            })
+    }
+
+    /// What `annual_expenses` at home would cost at `target`. Expenses are assumed to include the
+    /// sales tax where they're spent.
+    fn scale_expenses_to(
+        &self,
+        target: &Location,
+        annual_expenses: BigUR,
+    ) -> Result<BigUR, String> {
+        let ratio = self.living_costs_ratio(target)?;
+        let sales_tax_ratio: BigUR = cast_ratio(
+            (UR64::one() + target.sales_tax_rate()) / (UR64::one() + self.home.sales_tax_rate()),
+        );
+        Ok(annual_expenses * ratio * sales_tax_ratio)
+    }
+
+    /// The gross income at `target` that leaves `disposable` once the expenses, scaled to the
+    /// target, are paid, with both given in the citizen's currency.
+    ///
+    /// This is closed form, since the needed take-home is just the disposable plus the scaled
+    /// expenses, and every band of the brackets inverts exactly. The tax exempt income is assumed
+    /// to move along, so it's taken out of the take-home before inverting and added back after.
+    pub fn calc_gross_for_disposable_at(
+        &self,
+        target: &Location,
+        disposable: &BigUR,
+    ) -> Result<BigUR, String> {
+        let annual_expenses =
+            (self.monthly_expenses.clone() * BigUR::from_integer(BigUint::from(12u8))).0;
+        let net = disposable.clone() + self.scale_expenses_to(target, annual_expenses)?;
+        let exempt = &self.tax_exempt_income.0;
+        let taxable_net = if net > *exempt {
+            net - exempt.clone()
+        } else {
+            BigUR::zero()
+        };
+        Ok(self.calc_gross_at(target, &taxable_net) + exempt.clone())
+    }
+
+    /// The take-home income needed at `target` to keep the same standing as at home, given in the
+    /// citizen's currency.
+    pub fn estimate_equivalent_net_at(
+        &self,
+        target: &Location,
+        mode: AnalysisMode,
+    ) -> Result<BigUR, String> {
+        let net = self.calc_net_at(&self.home) + self.tax_exempt_income.0.clone();
         log::info!("Net income: {}", ApproxRatio(net.clone()));
 
         match mode {
             // Just do taxes, so stop here
-            AnalysisMode::PostTax => target.calc_gross(&net, self.status),
+            AnalysisMode::PostTax => Ok(net),
             AnalysisMode::Disposable => {
                 // TODO calculate disposable income
-                let annual_expenses =
-                    BigUR::from_integer(BigUint::from(12u8)) * self.monthly_expenses.clone();
+                let mut annual_expenses =
+                    (self.monthly_expenses.clone() * BigUR::from_integer(BigUint::from(12u8))).0;
                 if annual_expenses > net {
-                    panic!("Annual expenses are higher than income. Please watch your spending!");
+                    if !self.clamp_expenses {
+                        return Err(
+                            "Annual expenses are higher than income. Please watch your spending!"
+                                .to_owned(),
+                        );
+                    }
+                    log::warn!(
+                        "Capping annual expenses at the net income, leaving nothing to spare."
+                    );
+                    annual_expenses = net.clone();
                 }
                 let disposable = net - annual_expenses.clone();
-                let ratio = target.get_living_costs_factor() / self.home.get_living_costs_factor();
-                let target_net = disposable + annual_expenses * ratio;
-                target.calc_gross(&target_net, self.status)
+                Ok(disposable + self.scale_expenses_to(target, annual_expenses)?)
             }
         }
     }
+
+    /// The equivalent income is given in the citizen's currency, and includes the tax exempt income since
+    /// that is assumed to move along.
+    pub fn estimate_equivalent_income_at(
+        &self,
+        target: &Location,
+        mode: AnalysisMode,
+    ) -> Result<BigUR, String> {
+        let target_net = self.estimate_equivalent_net_at(target, mode)?;
+        let exempt = &self.tax_exempt_income.0;
+        let taxable_net = if target_net > *exempt {
+            target_net - exempt.clone()
+        } else {
+            BigUR::zero()
+        };
+        let gross = self.calc_gross_at(target, &taxable_net);
+        self.reproduces_net_at(target, &gross, &taxable_net);
+
+        Ok(gross + exempt.clone())
+    }
+
+    /// The brackets are inverted to find a gross, so this makes sure `gross` nets `taxable_net` at
+    /// `target` to within a cent, warning when it doesn't.
+    fn reproduces_net_at(&self, target: &Location, gross: &BigUR, taxable_net: &BigUR) -> bool {
+        let reproduced_net = self.calc_net_of_at(target, gross);
+        let error = if reproduced_net > *taxable_net {
+            reproduced_net.clone() - taxable_net.clone()
+        } else {
+            taxable_net.clone() - reproduced_net.clone()
+        };
+        if error > BigUR::new(BigUint::one(), BigUint::from(100u8)) {
+            log::warn!(
+                "The equivalent income nets {} rather than {}, so the bracket inversion is off.",
+                ApproxRatio(reproduced_net),
+                ApproxRatio(taxable_net.clone())
+            );
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brackets::TaxSystem;
+    use crate::loc::{CountryCode, State};
+
+    fn dollars(amount: u64) -> BigUR {
+        BigUR::from_integer(amount.into())
+    }
+
+    fn austin() -> Location {
+        Location::new(CountryCode::USA, State::TX, "Austin")
+    }
+
+    fn london() -> Location {
+        Location::new(CountryCode::GBR, State::ENG, "London")
+    }
+
+    fn earning(income: u64, home: Location) -> Citizen {
+        Citizen::new(Money(dollars(income)), MaritalStatus::Single, home)
+    }
+
+    #[test]
+    fn treaty_credit_lowers_the_taxes_below_both_countries_combined() {
+        let citizen = earning(100_000, austin());
+        let naive = citizen.calc_taxes() + citizen.calc_taxes_at(&london());
+        let credited = citizen.calc_taxes_with_treaty_credit(&london());
+        assert!(credited < naive);
+        assert_eq!(
+            credited,
+            std::cmp::max(citizen.calc_taxes(), citizen.calc_taxes_at(&london()))
+        );
+    }
+
+    #[test]
+    fn equivalent_income_reproduces_the_net_when_the_amt_wins_on_the_added_back_qbi() {
+        // A flat 5% at home brings the equivalent income into the AMT's reach, but keeps it below
+        // where the QBI deduction starts phasing out.
+        let target = austin().with_standard_deductions();
+        let home = target
+            .clone()
+            .with_extra_tax_system(TaxSystem::flat(UR64::new(5, 100)));
+        let citizen = Citizen {
+            qbi_income: Money(dollars(160_000)),
+            amt: true,
+            ..earning(165_000, home)
+        };
+        let gross = citizen
+            .estimate_equivalent_income_at(&target, AnalysisMode::PostTax)
+            .unwrap();
+        let moved = citizen.clone().with_income(gross.clone());
+        assert!(
+            moved.calc_taxes_at(&target)
+                > Citizen {
+                    amt: false,
+                    ..moved.clone()
+                }
+                .calc_taxes_at(&target)
+        );
+        let net = citizen.calc_net_at(&citizen.home);
+        let cent = BigUR::new(1u32.into(), 100u32.into());
+        assert!(moved.calc_net_at(&target) >= net);
+        assert!(moved.with_income(gross - cent).calc_net_at(&target) < net);
+    }
+
+    #[test]
+    fn half_a_year_in_california_and_texas_owes_half_of_each() {
+        let sf = Location::new(CountryCode::USA, State::CA, "SF").with_standard_deductions();
+        let target = austin().with_standard_deductions();
+        let citizen = earning(100_000, sf);
+        let home = citizen.calc_taxes();
+        let moved = citizen.calc_taxes_at(&target);
+        let half = BigUR::new(1u32.into(), 2u32.into());
+        assert_eq!(
+            citizen.calc_split_year_taxes(&target, UR64::new(1, 2)),
+            (home.clone() + moved.clone()) * half
+        );
+        assert_eq!(citizen.calc_split_year_taxes(&target, UR64::one()), home);
+        assert_eq!(citizen.calc_split_year_taxes(&target, UR64::zero()), moved);
+    }
+
+    #[test]
+    fn cheapest_tax_location_picks_austin_over_sf() {
+        let citizen = earning(150_000, austin());
+        let candidates = [Location::new(CountryCode::USA, State::CA, "SF"), austin()];
+        assert_eq!(citizen.cheapest_tax_location(&candidates), &austin());
+    }
+
+    #[test]
+    fn oregon_sales_tax_savings_offset_part_of_its_income_tax_over_texas() {
+        let citizen = Citizen {
+            monthly_expenses: Money(dollars(3_000)),
+            // Only the taxes are compared, not the cost of living.
+            adjust_cost_of_living: false,
+            ..earning(80_000, austin())
+        };
+        let portland = Location::new(CountryCode::USA, State::OR, "Portland");
+        let equivalent = |mode| {
+            citizen
+                .estimate_equivalent_income_at(&portland, mode)
+                .unwrap()
+        };
+        let disposable = equivalent(AnalysisMode::Disposable);
+        // Oregon's income tax is the larger of the two, so it still takes a raise to break even.
+        assert!(disposable > citizen.income.0);
+        // Without the sales tax on the expenses, the raise would have to cover the income tax alone.
+        assert!(disposable < equivalent(AnalysisMode::PostTax));
+    }
+
+    #[test]
+    fn expenses_above_the_net_are_rejected_unless_clamped_to_it() {
+        let overspending = Citizen {
+            monthly_expenses: Money(dollars(10_000)),
+            ..earning(100_000, austin())
+        };
+        assert!(overspending
+            .estimate_equivalent_net_at(&austin(), AnalysisMode::Disposable)
+            .is_err());
+        let clamped = Citizen {
+            clamp_expenses: true,
+            ..overspending
+        };
+        // Nothing is left to spare, so all it takes is covering the same expenses again.
+        let net = clamped.calc_net_at(&austin());
+        assert_eq!(
+            clamped.estimate_equivalent_net_at(&austin(), AnalysisMode::Disposable),
+            Ok(net)
+        );
+    }
+
+    #[test]
+    fn tax_exempt_income_is_earned_but_not_taxed() {
+        let citizen = earning(100_000, austin());
+        let with_bonds = Citizen {
+            tax_exempt_income: Money(dollars(20_000)),
+            ..citizen.clone()
+        };
+        assert_eq!(with_bonds.total_income(), dollars(120_000));
+        assert_eq!(with_bonds.calc_taxes(), citizen.calc_taxes());
+    }
+
+    #[test]
+    fn housing_heavy_expenses_cost_more_than_blended_in_a_high_housing_city() {
+        let blended = Citizen {
+            monthly_expenses: Money(dollars(3_000)),
+            ..earning(100_000, austin())
+        };
+        let housing_heavy = Citizen {
+            expense_shares: vec![(ExpenseCategory::Housing, UR64::new(80, 100))],
+            ..blended.clone()
+        };
+        let sf = Location::new(CountryCode::USA, State::CA, "SF");
+        let needed = |citizen: &Citizen| {
+            citizen
+                .estimate_equivalent_net_at(&sf, AnalysisMode::Disposable)
+                .unwrap()
+        };
+        // SF's housing is even further above Austin's than its costs overall.
+        assert!(needed(&housing_heavy) > needed(&blended));
+    }
+
+    #[test]
+    fn net_is_higher_in_austin_than_in_sf() {
+        let citizen = earning(100_000, austin());
+        let sf = Location::new(CountryCode::USA, State::CA, "SF");
+        assert!(citizen.calc_net_at(&austin()) > citizen.calc_net_at(&sf));
+        assert_eq!(
+            citizen.calc_net_at(&sf),
+            citizen.income.0.clone() - citizen.calc_taxes_at(&sf)
+        );
+    }
+
+    #[test]
+    fn equivalent_incomes_reproduce_their_net_under_the_usa_systems() {
+        let citizen = earning(100_000, austin());
+        for target in [
+            Location::new(CountryCode::USA, State::CA, "SF"),
+            Location::new(CountryCode::USA, State::OR, "Portland"),
+            austin().with_payroll_taxes(),
+        ] {
+            let net = citizen
+                .estimate_equivalent_net_at(&target, AnalysisMode::PostTax)
+                .unwrap();
+            let gross = citizen.calc_gross_at(&target, &net);
+            assert!(
+                citizen.reproduces_net_at(&target, &gross, &net),
+                "{}",
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn a_gross_off_from_a_broken_inversion_is_detected() {
+        let citizen = earning(100_000, austin());
+        let sf = Location::new(CountryCode::USA, State::CA, "SF");
+        let net = citizen.calc_net_at(&sf);
+        // A dollar off is what a bracket with the wrong flats would invert to.
+        let off = citizen.income.0.clone() + dollars(1);
+        assert!(citizen.reproduces_net_at(&sf, &citizen.income.0, &net));
+        assert!(!citizen.reproduces_net_at(&sf, &off, &net));
+    }
+
+    #[test]
+    fn half_the_net_budgeted_for_expenses_is_half_the_net_a_year() {
+        let citizen = earning(100_000, austin()).with_expenses_share_of_net(UR64::new(1, 2));
+        let annual_expenses =
+            citizen.monthly_expenses.0.clone() * BigUR::from_integer(BigUint::from(12u8));
+        assert_eq!(
+            annual_expenses.clone() * BigUR::from_integer(BigUint::from(2u8)),
+            citizen.calc_net_at(&austin())
+        );
+        // Whatever isn't spent is what's disposable, so the same half stays the same at home.
+        assert_eq!(
+            citizen.estimate_equivalent_net_at(&austin(), AnalysisMode::Disposable),
+            Ok(citizen.calc_net_at(&austin()))
+        );
+    }
+
+    #[test]
+    fn gross_solved_for_a_disposable_leaves_that_disposable() {
+        let sf = Location::new(CountryCode::USA, State::CA, "SF");
+        let mut citizen = earning(100_000, austin());
+        citizen.monthly_expenses = Money(dollars(3_000));
+        let disposable = dollars(40_000);
+        let gross = citizen
+            .calc_gross_for_disposable_at(&sf, &disposable)
+            .unwrap();
+        let expenses_at_sf = citizen.scale_expenses_to(&sf, dollars(36_000)).unwrap();
+        let moved = citizen.with_income(gross);
+        assert_eq!(moved.calc_net_at(&sf) - expenses_at_sf, disposable);
+    }
+
+    #[test]
+    fn turning_off_the_cost_of_living_adjustment_leaves_only_the_sales_tax() {
+        let portland = Location::new(CountryCode::USA, State::OR, "Portland");
+        let adjusted = Citizen {
+            monthly_expenses: Money(dollars(3_000)),
+            ..earning(80_000, austin())
+        };
+        let unadjusted = Citizen {
+            adjust_cost_of_living: false,
+            ..adjusted.clone()
+        };
+        assert_eq!(unadjusted.living_costs_ratio(&portland), Ok(BigUR::one()));
+        let sales_tax_ratio: BigUR = cast_ratio(
+            (UR64::one() + portland.sales_tax_rate()) / (UR64::one() + austin().sales_tax_rate()),
+        );
+        let annual_expenses = dollars(36_000);
+        let net = unadjusted.calc_net_at(&austin());
+        let equivalent_net = |citizen: &Citizen| {
+            citizen
+                .estimate_equivalent_net_at(&portland, AnalysisMode::Disposable)
+                .unwrap()
+        };
+        assert_eq!(
+            equivalent_net(&unadjusted),
+            net - annual_expenses.clone() + annual_expenses * sales_tax_ratio
+        );
+        // Portland costs more to live in, so adjusting for it asks for more.
+        assert!(equivalent_net(&adjusted) > equivalent_net(&unadjusted));
+    }
+
+    #[test]
+    fn hsa_contribution_comes_off_the_taxable_income_up_to_its_cap() {
+        let contributing = |amount, coverage| Citizen {
+            hsa_contribution: Money(dollars(amount)),
+            hsa_coverage: coverage,
+            ..earning(100_000, austin())
+        };
+        let taxable = |citizen: Citizen| {
+            citizen.taxable_income_at(&austin(), &citizen.income.0, MaritalStatus::Single)
+        };
+        assert_eq!(
+            taxable(contributing(2_000, HsaCoverage::SelfOnly)),
+            dollars(98_000)
+        );
+        // Only the 3,550 of self-only coverage is pre-tax, but a family can put in all 5,000.
+        assert_eq!(
+            taxable(contributing(5_000, HsaCoverage::SelfOnly)),
+            dollars(96_450)
+        );
+        assert_eq!(
+            taxable(contributing(5_000, HsaCoverage::Family)),
+            dollars(95_000)
+        );
+        let capped = contributing(5_000, HsaCoverage::SelfOnly);
+        assert!(capped.calc_taxes() < earning(100_000, austin()).calc_taxes());
+    }
+
+    #[test]
+    fn carryforward_above_the_gains_zeroes_their_taxes_and_carries_the_rest() {
+        let seattle = Location::new(CountryCode::USA, State::WA, "Seattle");
+        let investor = Citizen {
+            capital_gains: Money(dollars(300_000)),
+            ..earning(100_000, seattle.clone())
+        };
+        // 7% of the 50,000 above Washington's threshold.
+        assert_eq!(
+            investor.calc_capital_gains_taxes_at(&seattle),
+            dollars(3_500)
+        );
+        let carrying = Citizen {
+            loss_carryforward: Money(dollars(400_000)),
+            ..investor
+        };
+        assert_eq!(
+            carrying.calc_capital_gains_taxes_at(&seattle),
+            BigUR::zero()
+        );
+        assert_eq!(carrying.remaining_loss_carryforward(), dollars(100_000));
+    }
+
+    #[test]
+    fn career_taxes_sum_each_year_of_the_grown_income() {
+        let sf = Location::new(CountryCode::USA, State::CA, "SF");
+        let citizen = earning(100_000, austin());
+        let growth = UR64::new(5, 100);
+        let yearly_sum = |loc: &Location| {
+            [dollars(100_000), dollars(105_000), dollars(110_250)]
+                .iter()
+                .map(|income| citizen.calc_taxes_of_at(loc, income, MaritalStatus::Single))
+                .fold(BigUR::zero(), |total, taxes| total + taxes)
+        };
+        let sf_total = citizen.calc_career_taxes_at(&sf, growth, 3);
+        let austin_total = citizen.calc_career_taxes_at(&austin(), growth, 3);
+        assert_eq!(sf_total, yearly_sum(&sf));
+        assert_eq!(austin_total, yearly_sum(&austin()));
+        assert!(sf_total > austin_total);
+    }
+
+    #[test]
+    fn childcare_expenses_lower_the_taxes_up_to_the_credit_cap() {
+        let caring = |expenses, dependents| Citizen {
+            childcare_expenses: Money(dollars(expenses)),
+            care_dependents: dependents,
+            ..earning(100_000, austin())
+        };
+        let saved =
+            |citizen: Citizen| earning(100_000, austin()).calc_taxes() - citizen.calc_taxes();
+        // 20% of the expenses, which only count up to 3,000 for one dependent and 6,000 for more.
+        assert_eq!(saved(caring(2_000, 1)), dollars(400));
+        assert_eq!(saved(caring(5_000, 1)), dollars(600));
+        assert_eq!(saved(caring(10_000, 2)), dollars(1_200));
+        assert_eq!(saved(caring(5_000, 0)), dollars(0));
+    }
+
+    #[test]
+    fn next_unit_kept_is_one_less_the_combined_marginal_rate() {
+        let sf = Location::new(CountryCode::USA, State::CA, "SF");
+        let citizen = earning(100_000, austin());
+        // 24% federal, 10.23% for California and 1.5% for SF.
+        assert_eq!(
+            citizen.next_unit_kept_share_at(&sf),
+            UR64::one() - UR64::new(24, 100) - UR64::new(1_023, 10_000) - UR64::new(15, 1_000)
+        );
+        // The payroll taxes add another 7.65% below the wage base.
+        assert_eq!(
+            citizen.next_unit_kept_share_at(&sf.clone().with_payroll_taxes()),
+            citizen.next_unit_kept_share_at(&sf) - UR64::new(765, 10_000)
+        );
+        assert_eq!(
+            citizen.next_unit_kept_share_at(&austin()),
+            UR64::new(76, 100)
+        );
+    }
+
+    #[test]
+    fn qbi_income_comes_off_the_taxable_income_at_a_fifth_below_the_threshold() {
+        let owner = Citizen {
+            qbi_income: Money(dollars(50_000)),
+            ..earning(100_000, austin())
+        };
+        assert_eq!(
+            owner.taxable_income_at(&austin(), &owner.income.0, MaritalStatus::Single),
+            dollars(90_000)
+        );
+        // The 10,000 deduction comes off the 24% band.
+        assert_eq!(
+            earning(100_000, austin()).calc_taxes() - owner.calc_taxes(),
+            dollars(2_400)
+        );
+    }
+
+    #[test]
+    fn effective_rate_curve_never_falls_as_the_income_rises() {
+        let sf = Location::new(CountryCode::USA, State::CA, "SF");
+        let incomes: Vec<_> = (0..=40u64).map(|step| dollars(step * 25_000)).collect();
+        let curve = earning(100_000, austin()).effective_rate_curve_at(&sf, &incomes);
+        assert_eq!(curve.len(), incomes.len());
+        assert!(curve.iter().map(|(income, _)| income).eq(incomes.iter()));
+        assert_eq!(curve[0].1, BigUR::zero());
+        for pair in curve.windows(2) {
+            assert!(pair[0].1 <= pair[1].1, "falls after {}", pair[0].0);
+        }
+    }
+
+    #[test]
+    fn with_income_computes_the_taxes_of_the_new_income() {
+        let citizen = earning(100_000, austin());
+        // Caching the home taxes first, which the clone carries along.
+        let taxes = citizen.calc_taxes();
+        let raised = citizen.clone().with_income(dollars(150_000));
+        assert_eq!(raised.income, Money(dollars(150_000)));
+        assert_eq!(raised.calc_taxes(), earning(150_000, austin()).calc_taxes());
+        assert_eq!(citizen.calc_taxes(), taxes);
+        assert!(raised.calc_taxes() > taxes);
+    }
 }
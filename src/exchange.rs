@@ -0,0 +1,187 @@
+use isocountry::CountryCode;
+use num::traits::One;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path, sync::OnceLock};
+
+use crate::util::{cast_ratio, parse_rate, BigUR, UR64};
+
+pub type RatePair = (CurrencyCode, CurrencyCode);
+
+/// Rates replacing the built-in ones, set once at startup.
+static RATE_OVERRIDES: OnceLock<HashMap<RatePair, UR64>> = OnceLock::new();
+
+// ISO 4217 codes are kept as-is to match `CountryCode`.
+#[allow(clippy::upper_case_acronyms)]
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum CurrencyCode {
+    USD,
+    GBP,
+    EUR,
+}
+
+impl CurrencyCode {
+    pub fn of_country(country: &CountryCode) -> Self {
+        match country {
+            CountryCode::USA => Self::USD,
+            CountryCode::GBR => Self::GBP,
+            _ => panic!("Currency not implemented for country {:?}.", country),
+        }
+    }
+
+    pub fn symbol(self) -> char {
+        match self {
+            Self::USD => '$',
+            Self::GBP => '£',
+            Self::EUR => '€',
+        }
+    }
+
+    /// How many digits of the smaller unit there are to the main one, like the 2 of cents.
+    pub fn minor_units(self) -> u32 {
+        match self {
+            Self::USD | Self::GBP | Self::EUR => 2,
+        }
+    }
+
+    pub fn of_symbol(symbol: char) -> Result<Self, String> {
+        match symbol {
+            '$' => Ok(Self::USD),
+            '£' => Ok(Self::GBP),
+            '€' => Ok(Self::EUR),
+            _ => Err(format!(
+                "Currency not implemented for the symbol {:?}.",
+                symbol
+            )),
+        }
+    }
+}
+
+impl std::str::FromStr for CurrencyCode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "USD" => Ok(CurrencyCode::USD),
+            "GBP" => Ok(CurrencyCode::GBP),
+            "EUR" => Ok(CurrencyCode::EUR),
+            _ => Err(format!("Could not parse currency {:?}.", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for CurrencyCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CurrencyCode::USD => write!(f, "USD"),
+            CurrencyCode::GBP => write!(f, "GBP"),
+            CurrencyCode::EUR => write!(f, "EUR"),
+        }
+    }
+}
+
+/// Parses a pair like `"USD/GBP"`.
+fn parse_rate_pair(s: &str) -> Result<RatePair, String> {
+    match s.find('/') {
+        Some(idx) => Ok((s[..idx].trim().parse()?, s[idx + 1..].trim().parse()?)),
+        None => Err(format!(
+            "Expected a currency pair like \"USD/GBP\", not {:?}.",
+            s
+        )),
+    }
+}
+
+/// Parses a single rate like `"USD/GBP=0.75"`, which is the amount of GBP that a USD buys.
+pub fn parse_exchange_rate(s: &str) -> Result<(RatePair, UR64), String> {
+    match s.find('=') {
+        Some(idx) => Ok((parse_rate_pair(&s[..idx])?, parse_rate(&s[idx + 1..])?)),
+        None => Err(format!(
+            "Expected a rate like \"USD/GBP=0.75\", not {:?}.",
+            s
+        )),
+    }
+}
+
+/// Loads a JSON file mapping pairs like `"USD/GBP"` to rates like `"0.75"`.
+pub fn load_exchange_rates(path: impl AsRef<Path>) -> Result<HashMap<RatePair, UR64>, String> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read exchange rates from {:?}: {}", path, e))?;
+    let data: HashMap<String, String> = serde_json::from_str(&contents)
+        .map_err(|e| format!("Could not parse exchange rates from {:?}: {}", path, e))?;
+    data.iter()
+        .map(|(pair, rate)| Ok((parse_rate_pair(pair)?, parse_rate(rate)?)))
+        .collect()
+}
+
+/// Replaces the built-in rates with `rates` wherever they overlap. Only the first call counts.
+pub fn set_exchange_rates(rates: HashMap<RatePair, UR64>) {
+    if RATE_OVERRIDES.set(rates).is_err() {
+        log::warn!("Exchange rates were already set, so the new ones are ignored.");
+    }
+}
+
+fn overridden_rate(from: CurrencyCode, to: CurrencyCode) -> Option<UR64> {
+    let overrides = RATE_OVERRIDES.get()?;
+    overrides.get(&(from, to)).copied().or_else(|| {
+        overrides
+            .get(&(to, from))
+            .filter(|rate| **rate != UR64::from_integer(0))
+            .map(|rate| rate.recip())
+    })
+}
+
+/// The amount of `to` that a single unit of `from` buys. Rates that were set take precedence,
+/// including the inverse of the opposite pair.
+pub fn exchange_rate(from: CurrencyCode, to: CurrencyCode) -> BigUR {
+    if from == to {
+        return BigUR::one();
+    }
+    if let Some(rate) = overridden_rate(from, to) {
+        return cast_ratio(rate);
+    }
+    // TODO These rates go stale, so consider sourcing them from somewhere.
+    let rate = match (from, to) {
+        (CurrencyCode::USD, CurrencyCode::GBP) => UR64::new(73, 100),
+        (CurrencyCode::GBP, CurrencyCode::USD) => UR64::new(100, 73),
+        (CurrencyCode::USD, CurrencyCode::EUR) => UR64::new(85, 100),
+        (CurrencyCode::EUR, CurrencyCode::USD) => UR64::new(100, 85),
+        (CurrencyCode::GBP, CurrencyCode::EUR) => UR64::new(85, 73),
+        (CurrencyCode::EUR, CurrencyCode::GBP) => UR64::new(73, 85),
+        _ => unimplemented!("Exchange rate not implemented for {} to {}.", from, to),
+    };
+    cast_ratio(rate)
+}
+
+pub fn convert(amount: &BigUR, from: CurrencyCode, to: CurrencyCode) -> BigUR {
+    amount.clone() * exchange_rate(from, to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_file_loads_each_pair() {
+        let path = std::env::temp_dir().join("equinc-exchange-rates.json");
+        std::fs::write(&path, r#"{"USD/GBP": "0.5", "EUR/USD": "120%"}"#).unwrap();
+        let rates = load_exchange_rates(&path).unwrap();
+        assert_eq!(rates.len(), 2);
+        assert_eq!(
+            rates[&(CurrencyCode::USD, CurrencyCode::GBP)],
+            UR64::new(1, 2)
+        );
+        assert_eq!(
+            rates[&(CurrencyCode::EUR, CurrencyCode::USD)],
+            UR64::new(6, 5)
+        );
+    }
+
+    #[test]
+    fn single_rate_parses_its_pair() {
+        assert_eq!(
+            parse_exchange_rate("USD/GBP=0.75"),
+            Ok(((CurrencyCode::USD, CurrencyCode::GBP), UR64::new(3, 4)))
+        );
+        assert!(parse_exchange_rate("USD/GBP").is_err());
+    }
+}
@@ -1,13 +1,42 @@
 pub use isocountry::CountryCode;
 use maplit::hashmap;
-use num::traits::{One, Zero};
-use std::{cell::RefCell, collections::HashMap};
+use num::{
+    traits::{One, Zero},
+    BigUint,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{cell::RefCell, collections::HashMap, sync::OnceLock};
 
 use crate::brackets::{MaritalStatus, TaxSystem};
+use crate::exchange::CurrencyCode;
 use crate::util::*;
 
-// TODO This can become `const` eventually.
-fn usa_tax_system() -> TaxSystem {
+/// Each built-in system is only built once per process, since the flats aren't free to derive.
+macro_rules! cached_tax_system {
+    ($name:ident, $build:ident) => {
+        fn $name() -> TaxSystem {
+            static SYSTEM: OnceLock<TaxSystem> = OnceLock::new();
+            SYSTEM.get_or_init($build).clone()
+        }
+    };
+}
+
+cached_tax_system!(usa_tax_system, build_usa_tax_system);
+cached_tax_system!(
+    usa_alternative_minimum_tax_system,
+    build_usa_alternative_minimum_tax_system
+);
+cached_tax_system!(usa_payroll_tax_system, build_usa_payroll_tax_system);
+cached_tax_system!(gbr_tax_system, build_gbr_tax_system);
+cached_tax_system!(sct_tax_system, build_sct_tax_system);
+cached_tax_system!(ca_tax_system, build_ca_tax_system);
+cached_tax_system!(or_tax_system, build_or_tax_system);
+cached_tax_system!(
+    wa_capital_gains_tax_system,
+    build_wa_capital_gains_tax_system
+);
+
+fn build_usa_tax_system() -> TaxSystem {
     let taxes_by_bracket = vec![
         UR64::new(10, 100),
         UR64::new(12, 100),
@@ -27,146 +56,1737 @@ fn usa_tax_system() -> TaxSystem {
     TaxSystem::new(ranges_by_status)
 }
 
-pub fn country_tax_system(country: &CountryCode) -> Option<TaxSystem> {
+/// Lays the AMT exemption and its phaseout out as brackets over gross income, since the
+/// exemption shrinks by 25 cents for every dollar above the phaseout threshold.
+///
+/// Assumes the 28% rate kicks in before the phaseout starts, which holds for every status.
+fn amt_brackets(
+    exemption: u64,
+    phaseout_start: u64,
+    high_rate_start: u64,
+) -> (Vec<u64>, Vec<UR64>) {
+    assert!(exemption + high_rate_start < phaseout_start);
+    let separators = vec![
+        exemption,
+        exemption + high_rate_start,
+        phaseout_start,
+        phaseout_start + 4 * exemption,
+    ];
+    let rates = vec![
+        UR64::zero(),
+        UR64::new(26, 100),
+        UR64::new(28, 100),
+        // Every dollar in the phaseout is taxed along with the quarter dollar of exemption it removes.
+        UR64::new(28, 100) * UR64::new(5, 4),
+        UR64::new(28, 100),
+    ];
+    (separators, rates)
+}
+
+/// A simplified Alternative Minimum Tax. The AMT income is the income with the deductions added
+/// back, and nothing else is adjusted.
+fn build_usa_alternative_minimum_tax_system() -> TaxSystem {
+    let brackets_by_status = hashmap! {
+        MaritalStatus::Single          => amt_brackets( 72_900,   518_400, 197_900),
+        MaritalStatus::Joint           => amt_brackets(113_400, 1_036_800, 197_900),
+        MaritalStatus::Separate        => amt_brackets( 56_700,   518_400,  98_950),
+        MaritalStatus::HeadOfHousehold => amt_brackets( 72_900,   518_400, 197_900),
+    };
+
+    TaxSystem::new(brackets_by_status)
+}
+
+/// Social Security stops at the wage base, while the Additional Medicare Tax starts at a threshold
+/// that depends on the status.
+fn payroll_brackets(wage_base: u64, additional_medicare_threshold: u64) -> (Vec<u64>, Vec<UR64>) {
+    let social_security = UR64::new(62, 1_000);
+    let medicare = UR64::new(145, 10_000);
+    let additional_medicare = UR64::new(9, 1_000);
+    let mut separators = vec![wage_base, additional_medicare_threshold];
+    separators.sort_unstable();
+    separators.dedup();
+    let rate_above = |floor: Option<u64>| {
+        let mut rate = medicare;
+        if floor.is_none_or(|floor| floor < wage_base) {
+            rate += social_security;
+        }
+        if floor.is_some_and(|floor| floor >= additional_medicare_threshold) {
+            rate += additional_medicare;
+        }
+        rate
+    };
+    let rates = std::iter::once(None)
+        .chain(separators.iter().copied().map(Some))
+        .map(rate_above)
+        .collect();
+    (separators, rates)
+}
+
+/// FICA for employees in 2020, without the employer's half.
+fn build_usa_payroll_tax_system() -> TaxSystem {
+    let wage_base = 137_700;
+    let additional_medicare_thresholds = hashmap! {
+        MaritalStatus::Single          => 200_000,
+        MaritalStatus::Joint           => 250_000,
+        MaritalStatus::Separate        => 125_000,
+        MaritalStatus::HeadOfHousehold => 200_000,
+    };
+    let brackets_by_status = additional_medicare_thresholds
+        .into_iter()
+        .map(|(status, threshold)| (status, payroll_brackets(wage_base, threshold)))
+        .collect();
+
+    TaxSystem::new(brackets_by_status)
+}
+
+fn build_gbr_tax_system() -> TaxSystem {
+    // The personal allowance is lost at a pound for every two above £100,000, which taxes that
+    // stretch at 60%.
+    let taxes_by_bracket = vec![
+        UR64::zero(),
+        UR64::new(20, 100),
+        UR64::new(40, 100),
+        UR64::new(60, 100),
+        UR64::new(40, 100),
+        UR64::new(45, 100),
+    ];
+    let separators = vec![12_500, 50_000, 100_000, 125_000, 150_000];
+    // Income is taxed individually, so the filing status doesn't matter.
+    let ranges_by_status: HashMap<_, (Vec<u64>, _)> = hashmap! {
+        MaritalStatus::Single =>          (separators.clone(), taxes_by_bracket.clone()),
+        MaritalStatus::Joint =>           (separators.clone(), taxes_by_bracket.clone()),
+        MaritalStatus::Separate =>        (separators.clone(), taxes_by_bracket.clone()),
+        MaritalStatus::HeadOfHousehold => (separators.clone(), taxes_by_bracket.clone()),
+    };
+
+    TaxSystem::new(ranges_by_status)
+}
+
+/// Scotland's 2020-21 bands, which its residents pay in place of the rest of the UK's. The personal
+/// allowance is the UK's, and is lost the same way, which taxes that stretch at 61.5%.
+fn build_sct_tax_system() -> TaxSystem {
+    let taxes_by_bracket = vec![
+        UR64::zero(),
+        UR64::new(19, 100),
+        UR64::new(20, 100),
+        UR64::new(21, 100),
+        UR64::new(41, 100),
+        UR64::new(41, 100) * UR64::new(3, 2),
+        UR64::new(41, 100),
+        UR64::new(46, 100),
+    ];
+    let separators = vec![12_500, 14_585, 25_158, 43_430, 100_000, 125_000, 150_000];
+    let ranges_by_status: HashMap<_, (Vec<u64>, _)> = hashmap! {
+        MaritalStatus::Single =>          (separators.clone(), taxes_by_bracket.clone()),
+        MaritalStatus::Joint =>           (separators.clone(), taxes_by_bracket.clone()),
+        MaritalStatus::Separate =>        (separators.clone(), taxes_by_bracket.clone()),
+        MaritalStatus::HeadOfHousehold => (separators.clone(), taxes_by_bracket.clone()),
+    };
+
+    TaxSystem::new(ranges_by_status)
+}
+
+pub fn supported_countries() -> Vec<CountryCode> {
+    vec![CountryCode::USA, CountryCode::GBR]
+}
+
+/// A flat contribution levied on income separately from the income tax, which is only charged
+/// when asked for.
+fn country_social_contribution_rate(country: &CountryCode) -> Option<UR64> {
+    match country {
+        // A hypothetical flat 2% on all income. National Insurance is charged on earnings above
+        // its own thresholds instead, and isn't modeled.
+        CountryCode::GBR => Some(UR64::new(2, 100)),
+        _ => None,
+    }
+}
+
+fn country_income_tax_system(country: &CountryCode) -> TaxSystem {
     match country {
-        CountryCode::USA => Some(usa_tax_system()),
+        CountryCode::USA => usa_tax_system(),
+        CountryCode::GBR => gbr_tax_system(),
         _ => panic!("Tax rates not implemented for country {:?}.", country),
     }
 }
 
+pub fn country_tax_system(country: &CountryCode) -> Option<TaxSystem> {
+    Some(country_income_tax_system(country))
+}
+
+/// The 2020 standard deduction off the country's income tax, where there's one. The UK's personal
+/// allowance is already the bottom band of its brackets.
+fn country_standard_deduction(country: &CountryCode, status: MaritalStatus) -> Option<BigUR> {
+    let deduction: u32 = match (country, status) {
+        (CountryCode::USA, MaritalStatus::Single) => 12_400,
+        (CountryCode::USA, MaritalStatus::Joint) => 24_800,
+        (CountryCode::USA, MaritalStatus::Separate) => 12_400,
+        (CountryCode::USA, MaritalStatus::HeadOfHousehold) => 18_650,
+        _ => return None,
+    };
+    Some(BigUR::from_integer(deduction.into()))
+}
+
+fn merge_social_contribution(country: &CountryCode, income_tax: TaxSystem) -> TaxSystem {
+    match country_social_contribution_rate(country) {
+        Some(rate) => TaxSystem::merge(income_tax, TaxSystem::flat(rate)),
+        None => income_tax,
+    }
+}
+
+fn country_sales_tax_rate(country: &CountryCode) -> UR64 {
+    match country {
+        // Sales taxes are left to the states.
+        CountryCode::USA => UR64::zero(),
+        CountryCode::GBR => UR64::new(20, 100),
+        _ => panic!("Sales tax not implemented for country {:?}.", country),
+    }
+}
+
+/// The system replacing the regular one when it produces a higher tax.
+pub fn country_alternative_tax_system(country: &CountryCode) -> Option<TaxSystem> {
+    match country {
+        CountryCode::USA => Some(usa_alternative_minimum_tax_system()),
+        _ => None,
+    }
+}
+
+/// Taxes on wages that are owed on top of the income taxes, whichever system those use.
+pub fn country_payroll_tax_system(country: &CountryCode) -> Option<TaxSystem> {
+    match country {
+        CountryCode::USA => Some(usa_payroll_tax_system()),
+        _ => None,
+    }
+}
+
+/// The flat rate that bonuses and other supplemental wages are withheld at, where there's one.
+fn country_supplemental_withholding_rate(country: &CountryCode) -> Option<UR64> {
+    match country {
+        CountryCode::USA => Some(UR64::new(22, 100)),
+        _ => None,
+    }
+}
+
+/// The most of the state and local taxes that can be deducted from the country's taxable income.
+fn country_local_tax_deduction_cap(country: &CountryCode, status: MaritalStatus) -> Option<BigUR> {
+    let cap: u32 = match (country, status) {
+        // The SALT cap introduced in 2018.
+        (CountryCode::USA, MaritalStatus::Separate) => 5_000,
+        (CountryCode::USA, _) => 10_000,
+        _ => return None,
+    };
+    Some(BigUR::from_integer(cap.into()))
+}
+
+fn build_ca_tax_system() -> TaxSystem {
+    let taxes_by_bracket = vec![
+        UR64::new(11, 1_000),
+        UR64::new(22, 1_000),
+        UR64::new(44, 1_000),
+        UR64::new(66, 1_000),
+        UR64::new(88, 1_000),
+        UR64::new(10_23, 10_000),
+        UR64::new(11_33, 10_000),
+        UR64::new(12_43, 10_000),
+        UR64::new(13_53, 10_000),
+        UR64::new(14_63, 10_000),
+    ];
+    let brackets_by_status: HashMap<_, (Vec<u64>, _)> = hashmap! {
+        MaritalStatus::Single          => (vec![ 8_809, 20_883, 32_960, 45_753,  57_824, 295_373, 354_445,   590_742, 1_000_000], taxes_by_bracket.clone()),
+        MaritalStatus::Joint           => (vec![17_618, 41_766, 65_920, 91_506, 115_648, 590_746, 708_890, 1_000_000, 1_181_484], taxes_by_bracket.clone()),
+        MaritalStatus::Separate        => (vec![ 8_809, 20_883, 32_960, 45_753,  57_824, 295_373, 354_445,   590_742, 1_000_000], taxes_by_bracket.clone()),
+        MaritalStatus::HeadOfHousehold => (vec![17_629, 41_768, 53_843, 66_636,  78_710, 401_705, 482_047,   803_410, 1_000_000], taxes_by_bracket.clone()),
+    };
+
+    TaxSystem::new(brackets_by_status)
+}
+
+fn build_or_tax_system() -> TaxSystem {
+    let taxes_by_bracket = vec![
+        UR64::new(4_75, 10_000),
+        UR64::new(6_75, 10_000),
+        UR64::new(8_75, 10_000),
+        UR64::new(9_90, 10_000),
+    ];
+    let brackets_by_status: HashMap<_, (Vec<u64>, _)> = hashmap! {
+        MaritalStatus::Single          => (vec![3_650,  9_200, 125_000], taxes_by_bracket.clone()),
+        MaritalStatus::Joint           => (vec![7_300, 18_400, 250_000], taxes_by_bracket.clone()),
+        MaritalStatus::Separate        => (vec![3_650,  9_200, 125_000], taxes_by_bracket.clone()),
+        MaritalStatus::HeadOfHousehold => (vec![7_300, 18_400, 250_000], taxes_by_bracket.clone()),
+    };
+
+    TaxSystem::new(brackets_by_status)
+}
+
+/// Washington's 2022 tax on long-term capital gains, which exempts the first $250,000 regardless
+/// of the status.
+fn build_wa_capital_gains_tax_system() -> TaxSystem {
+    let taxes_by_bracket = vec![UR64::zero(), UR64::new(7, 100)];
+    let brackets_by_status: HashMap<_, (Vec<u64>, _)> = hashmap! {
+        MaritalStatus::Single          => (vec![250_000], taxes_by_bracket.clone()),
+        MaritalStatus::Joint           => (vec![250_000], taxes_by_bracket.clone()),
+        MaritalStatus::Separate        => (vec![250_000], taxes_by_bracket.clone()),
+        MaritalStatus::HeadOfHousehold => (vec![250_000], taxes_by_bracket.clone()),
+    };
+
+    TaxSystem::new(brackets_by_status)
+}
+
+// The UK's constituent countries use their ISO 3166-2 codes.
+#[allow(clippy::upper_case_acronyms)]
 #[non_exhaustive]
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum State {
     CA,
     TX,
+    OR,
+    WA,
+    IL,
+    IN,
+    MI,
+    ENG,
+    SCT,
+}
+
+pub fn supported_states() -> Vec<State> {
+    vec![
+        State::CA,
+        State::TX,
+        State::OR,
+        State::WA,
+        State::IL,
+        State::IN,
+        State::MI,
+        State::ENG,
+        State::SCT,
+    ]
 }
 
 impl State {
-    fn tax_system(self) -> Option<TaxSystem> {
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Self::CA => "California",
+            Self::TX => "Texas",
+            Self::OR => "Oregon",
+            Self::WA => "Washington",
+            Self::IL => "Illinois",
+            Self::IN => "Indiana",
+            Self::MI => "Michigan",
+            Self::ENG => "England",
+            Self::SCT => "Scotland",
+            #[allow(unreachable_patterns)]
+            _ => unimplemented!("Display name not implemented for state {:?}.", self),
+        }
+    }
+
+    /// The country the state is part of.
+    pub fn country(self) -> CountryCode {
         match self {
-            Self::CA => {
-                let taxes_by_bracket = vec![
-                    UR64::new(1_1, 1_000),
-                    UR64::new(2_2, 1_000),
-                    UR64::new(4_4, 1_000),
-                    UR64::new(6_6, 1_000),
-                    UR64::new(8_8, 1_000),
-                    UR64::new(10_23, 10_000),
-                    UR64::new(11_33, 10_000),
-                    UR64::new(12_43, 10_000),
-                    UR64::new(13_53, 10_000),
-                    UR64::new(14_63, 10_000),
-                ];
-                let brackets_by_status: HashMap<_, (Vec<u64>, _)> = hashmap! {
-                    MaritalStatus::Single          => (vec![ 8_809, 20_883, 32_960, 45_753,  57_824, 295_373, 354_445,   590_742, 1_000_000], taxes_by_bracket.clone()),
-                    MaritalStatus::Joint           => (vec![17_618, 41_766, 65_920, 91_506, 115_648, 590_746, 708_890, 1_000_000, 1_181_484], taxes_by_bracket.clone()),
-                    MaritalStatus::Separate        => (vec![ 8_809, 20_883, 32_960, 45_753,  57_824, 295_373, 354_445,   590_742, 1_000_000], taxes_by_bracket.clone()),
-                    MaritalStatus::HeadOfHousehold => (vec![17_629, 41_768, 53_843, 66_636,  78_710, 401_705, 482_047,   803_410, 1_000_000], taxes_by_bracket.clone()),
-                };
-
-                Some(TaxSystem::new(brackets_by_status))
+            Self::CA | Self::TX | Self::OR | Self::WA | Self::IL | Self::IN | Self::MI => {
+                CountryCode::USA
             }
+            Self::ENG | Self::SCT => CountryCode::GBR,
+            #[allow(unreachable_patterns)]
+            _ => unimplemented!("Country not implemented for state {:?}.", self),
+        }
+    }
+
+    /// The statewide rate, without any local additions.
+    fn sales_tax_rate(self) -> UR64 {
+        match self {
+            Self::CA => UR64::new(7_25, 10_000),
+            Self::TX => UR64::new(6_25, 10_000),
+            Self::OR => UR64::zero(),
+            Self::WA => UR64::new(6_5, 1_000),
+            Self::IL => UR64::new(6_25, 10_000),
+            Self::IN => UR64::new(7, 100),
+            Self::MI => UR64::new(6, 100),
+            // VAT is charged by the UK as a whole.
+            Self::ENG => UR64::zero(),
+            Self::SCT => UR64::zero(),
+            #[allow(unreachable_patterns)]
+            _ => panic!("Sales tax not implemented for state {:?}.", self),
+        }
+    }
+
+    fn tax_system(self) -> Option<TaxSystem> {
+        match self {
+            Self::CA => Some(ca_tax_system()),
             Self::TX => None,
+            Self::OR => Some(or_tax_system()),
+            // Wages aren't taxed, only capital gains.
+            Self::WA => None,
+            // The 2020 flat rates, before any county taxes.
+            Self::IL => Some(TaxSystem::flat(UR64::new(4_95, 10_000))),
+            Self::IN => Some(TaxSystem::flat(UR64::new(3_23, 10_000))),
+            Self::MI => Some(TaxSystem::flat(UR64::new(4_25, 10_000))),
+            Self::ENG => None,
+            // Scotland's taxes replace the country's income tax instead of adding to it.
+            Self::SCT => None,
             #[allow(unreachable_patterns)]
             _ => panic!("Tax rates not implemented for state {:?}.", self),
         }
     }
+
+    /// The 2020 standard deduction off the state's income tax, where there's one. States without
+    /// an income tax have nothing to deduct from, and the flat ones only have exemptions.
+    fn standard_deduction(self, status: MaritalStatus) -> Option<BigUR> {
+        let deduction: u32 = match (self, status) {
+            (Self::CA, MaritalStatus::Single | MaritalStatus::Separate) => 4_601,
+            (Self::CA, MaritalStatus::Joint | MaritalStatus::HeadOfHousehold) => 9_202,
+            (Self::OR, MaritalStatus::Single | MaritalStatus::Separate) => 2_315,
+            (Self::OR, MaritalStatus::Joint) => 4_630,
+            (Self::OR, MaritalStatus::HeadOfHousehold) => 3_725,
+            _ => return None,
+        };
+        Some(BigUR::from_integer(deduction.into()))
+    }
+
+    /// Income tax bands that the state's residents pay in place of the country's.
+    fn income_tax_system(self) -> Option<TaxSystem> {
+        match self {
+            Self::SCT => Some(sct_tax_system()),
+            _ => None,
+        }
+    }
+
+    /// Whether residents of either state working in the other only pay taxes to the one they live
+    /// in.
+    fn has_reciprocity_with(self, other: State) -> bool {
+        RECIPROCITY
+            .iter()
+            .any(|&(lhs, rhs)| (lhs, rhs) == (self, other) || (lhs, rhs) == (other, self))
+    }
+
+    /// Taxes on capital gains that are charged separately from the income taxes.
+    fn capital_gains_tax_system(self) -> Option<TaxSystem> {
+        match self {
+            Self::WA => Some(wa_capital_gains_tax_system()),
+            _ => None,
+        }
+    }
 }
 
+/// Pairs of states with a reciprocity agreement, among the supported ones.
+const RECIPROCITY: &[(State, State)] = &[(State::IL, State::MI), (State::IN, State::MI)];
+
 impl std::str::FromStr for State {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "CA" | "California" => Ok(State::CA),
             "TX" | "Texas" => Ok(State::TX),
-            _ => Err(format!("Could not parse country {:?}", s)),
+            "OR" | "Oregon" => Ok(State::OR),
+            "WA" | "Washington" => Ok(State::WA),
+            "IL" | "Illinois" => Ok(State::IL),
+            "IN" | "Indiana" => Ok(State::IN),
+            "MI" | "Michigan" => Ok(State::MI),
+            "ENG" | "England" => Ok(State::ENG),
+            "SCT" | "Scotland" => Ok(State::SCT),
+            _ => {
+                let supported: Vec<_> = supported_states()
+                    .iter()
+                    .map(|state| state.to_string())
+                    .collect();
+                Err(format!(
+                    "Could not parse state {:?}, expected one of {}",
+                    s,
+                    supported.join(", ")
+                ))
+            }
         }
     }
 }
 
+impl std::fmt::Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let code = match self {
+            State::CA => "CA",
+            State::TX => "TX",
+            State::OR => "OR",
+            State::WA => "WA",
+            State::IL => "IL",
+            State::IN => "IN",
+            State::MI => "MI",
+            State::ENG => "ENG",
+            State::SCT => "SCT",
+            #[allow(unreachable_patterns)]
+            _ => unimplemented!("Display not implemented for state {:?}.", self),
+        };
+        write!(f, "{}", code)
+    }
+}
+
+/// City systems for places without built-in data, registered once at startup.
+static LOCATION_REGISTRY: OnceLock<LocationRegistry> = OnceLock::new();
+
+/// Tax systems for cities, which are used in place of the built-in ones.
+#[derive(Debug, Default)]
+pub struct LocationRegistry(HashMap<(CountryCode, State, String), TaxSystem>);
+
+impl LocationRegistry {
+    pub fn insert(
+        &mut self,
+        country: CountryCode,
+        state: State,
+        city: impl Into<String>,
+        system: TaxSystem,
+    ) {
+        self.0.insert((country, state, city.into()), system);
+    }
+
+    fn get(&self, country: CountryCode, state: State, city: &str) -> Option<&TaxSystem> {
+        self.0.get(&(country, state, city.to_owned()))
+    }
+}
+
+/// Registers custom city systems for every location made afterwards. Only the first call counts.
+pub fn set_location_registry(registry: LocationRegistry) {
+    if LOCATION_REGISTRY.set(registry).is_err() {
+        log::warn!("Locations were already registered, so the new ones are ignored.");
+    }
+}
+
+#[derive(Clone)]
+struct CityData {
+    /// Every name the city goes by, with the main one first.
+    names: &'static [&'static str],
+    /// The flat tax on income as a numerator and denominator, if there is one.
+    flat_rate: Option<(u64, u64)>,
+}
+
+/// Every city with built-in data. Adding a city only takes a new row.
+const CITY_TABLE: &[CityData] = &[
+    CityData {
+        names: &["SF", "San Francisco"],
+        flat_rate: Some((15, 1_000)),
+    },
+    CityData {
+        names: &["Austin", "AUS"],
+        flat_rate: None,
+    },
+    CityData {
+        names: &["Portland", "PDX"],
+        flat_rate: None,
+    },
+    CityData {
+        names: &["Seattle", "SEA"],
+        flat_rate: None,
+    },
+    CityData {
+        names: &["Chicago", "CHI"],
+        flat_rate: None,
+    },
+    CityData {
+        // Marion County's income tax.
+        names: &["Indianapolis", "IND"],
+        flat_rate: Some((2_02, 10_000)),
+    },
+    CityData {
+        // The rate for residents.
+        names: &["Detroit", "DTW"],
+        flat_rate: Some((24, 1_000)),
+    },
+    CityData {
+        names: &["London"],
+        flat_rate: None,
+    },
+    CityData {
+        names: &["Edinburgh"],
+        flat_rate: None,
+    },
+];
+
+/// Names are matched regardless of case and surrounding whitespace.
+fn normalize_city_name(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// The rates of `table` keyed on every normalized name.
+fn index_city_rates(table: &[CityData]) -> HashMap<String, Option<UR64>> {
+    table
+        .iter()
+        .flat_map(|city| {
+            let rate = city.flat_rate.map(|(numer, denom)| UR64::new(numer, denom));
+            city.names
+                .iter()
+                .map(move |name| (normalize_city_name(name), rate))
+        })
+        .collect()
+}
+
+/// `CITY_TABLE` keyed on every normalized name.
+fn city_rates() -> &'static HashMap<String, Option<UR64>> {
+    static RATES: OnceLock<HashMap<String, Option<UR64>>> = OnceLock::new();
+    RATES.get_or_init(|| index_city_rates(CITY_TABLE))
+}
+
+/// Only one of the names that each city goes by.
+pub fn supported_cities() -> Vec<&'static str> {
+    CITY_TABLE.iter().map(|city| city.names[0]).collect()
+}
+
 fn city_tax_system<S: AsRef<str>>(city: S) -> Option<TaxSystem> {
-    match city.as_ref() {
-        "San Francisco" | "SF" => Some(TaxSystem::flat(UR64::new(15, 1000))),
-        "Austin" | "AUS" => None,
-        _ => panic!("Tax rates not implemented for city {:?}.", city.as_ref()),
+    match city_rates().get(&normalize_city_name(city.as_ref())) {
+        Some(rate) => rate.map(TaxSystem::flat),
+        None => panic!("Tax rates not implemented for city {:?}.", city.as_ref()),
     }
 }
 
-// TODO Currently USA specific, but perhaps expand later?
+/// Runs every built-in system through `TaxSystem::problems`, labelling each problem with the
+/// jurisdiction it came from.
+pub fn check_built_in_tax_systems() -> Vec<String> {
+    let mut systems = vec![];
+    for country in supported_countries() {
+        systems.push((country.alpha3().to_owned(), country_tax_system(&country)));
+        systems.push((
+            format!("{} (alternative)", country.alpha3()),
+            country_alternative_tax_system(&country),
+        ));
+        systems.push((
+            format!("{} (payroll)", country.alpha3()),
+            country_payroll_tax_system(&country),
+        ));
+    }
+    for state in supported_states() {
+        systems.push((state.to_string(), state.tax_system()));
+        systems.push((format!("{} (income tax)", state), state.income_tax_system()));
+        systems.push((
+            format!("{} (capital gains)", state),
+            state.capital_gains_tax_system(),
+        ));
+    }
+    for city in supported_cities() {
+        systems.push((city.to_owned(), city_tax_system(city)));
+    }
+
+    let mut problems = vec![];
+    for (name, system) in systems {
+        if let Some(system) = system {
+            log::debug!("Checking the tax system of {}.", name);
+            problems.extend(
+                system
+                    .problems()
+                    .into_iter()
+                    .map(|problem| format!("{}: {}", name, problem)),
+            );
+            if TaxSystem::from_brackets_map(system.to_brackets_map()) != system {
+                problems.push(format!("{}: changes when rebuilt from its brackets", name));
+            }
+        }
+    }
+    problems
+}
+
+/// The expenses that scale differently between cities. Anything else scales with the blended
+/// factor.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ExpenseCategory {
+    Housing,
+    Food,
+    Transport,
+}
+
+impl std::str::FromStr for ExpenseCategory {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "housing" => Ok(ExpenseCategory::Housing),
+            "food" => Ok(ExpenseCategory::Food),
+            "transport" => Ok(ExpenseCategory::Transport),
+            _ => Err(format!("Could not parse the expense category {:?}.", s)),
+        }
+    }
+}
+
+/// Who a health savings account covers, which sets how much can be put in it.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Default, Serialize, Deserialize)]
+pub enum HsaCoverage {
+    #[default]
+    SelfOnly,
+    Family,
+}
+
+impl std::str::FromStr for HsaCoverage {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "self" => Ok(HsaCoverage::SelfOnly),
+            "family" => Ok(HsaCoverage::Family),
+            _ => Err(format!("Failed to understand HSA coverage {:?}.", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for HsaCoverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HsaCoverage::SelfOnly => write!(f, "self"),
+            HsaCoverage::Family => write!(f, "family"),
+        }
+    }
+}
+
+/// The most that can be put in a health savings account before taxes, where there are any.
+fn country_hsa_limit(country: &CountryCode, coverage: HsaCoverage) -> Option<BigUR> {
+    let limit: u32 = match (country, coverage) {
+        // The 2020 limits, without the catch-up for those 55 and older.
+        (CountryCode::USA, HsaCoverage::SelfOnly) => 3_550,
+        (CountryCode::USA, HsaCoverage::Family) => 7_100,
+        _ => return None,
+    };
+    Some(BigUR::from_integer(limit.into()))
+}
+
+/// The share of the qualifying childcare expenses credited against the taxes, where there's a
+/// credit for them.
+fn country_dependent_care_credit_rate(country: &CountryCode) -> Option<UR64> {
+    match country {
+        // The lowest rate of the Child and Dependent Care Credit, which applies above $43,000 of
+        // income. The higher rates for lower incomes aren't modeled.
+        CountryCode::USA => Some(UR64::new(20, 100)),
+        _ => None,
+    }
+}
+
+/// The most childcare expenses that qualify for the credit with `dependents` in care.
+fn country_dependent_care_expense_cap(country: &CountryCode, dependents: u32) -> BigUR {
+    let cap: u32 = match (country, dependents) {
+        (CountryCode::USA, 0) => 0,
+        (CountryCode::USA, 1) => 3_000,
+        (CountryCode::USA, _) => 6_000,
+        _ => 0,
+    };
+    BigUR::from_integer(cap.into())
+}
+
+/// Where the deduction on qualified business income starts phasing out, and over how much more
+/// income it's gone, where there's a deduction.
+fn country_qbi_phaseout(country: &CountryCode, status: MaritalStatus) -> Option<(BigUR, BigUR)> {
+    let (threshold, range): (u32, u32) = match (country, status) {
+        // The 2020 thresholds.
+        (CountryCode::USA, MaritalStatus::Joint) => (326_600, 100_000),
+        (CountryCode::USA, _) => (163_300, 50_000),
+        _ => return None,
+    };
+    Some((
+        BigUR::from_integer(threshold.into()),
+        BigUR::from_integer(range.into()),
+    ))
+}
+
 // TODO think about iso3166-2
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Location {
     pub country: CountryCode,
     pub state: State,
     pub city: String,
-    // TODO cache the final tax bracket
+    /// Taxes not covered by the built-in data, merged on top of them.
+    extra_tax_systems: Vec<TaxSystem>,
+    /// Whether the country's payroll taxes, like FICA, are owed too.
+    payroll_taxes: bool,
+    /// Whether the city's taxes are deliberately left out, even when they're known.
+    ignore_city_tax: bool,
+    /// Where the work is, when it's in another state than the one lived in.
+    work_state: Option<State>,
+    standard_deductions: bool,
+    social_contribution: bool,
+    bracket_index: Option<UR64>,
     cached_merged_tax_bracket: RefCell<Option<TaxSystem>>,
 }
 
+fn merge_tax_systems(brackets: Vec<Option<TaxSystem>>) -> Option<TaxSystem> {
+    let mut merged = None;
+    for (i, brackets) in brackets.into_iter().enumerate() {
+        log::debug!("Merging bracket {}", i);
+        if let Some(brackets) = brackets {
+            if let Some(merged_brackets) = merged {
+                merged = Some(TaxSystem::merge(merged_brackets, brackets));
+            } else {
+                merged = Some(brackets);
+            }
+        }
+    }
+    log::debug!("Final brackets: {:?}", merged);
+    merged
+}
+
 impl Location {
-    fn tax_system(&self) -> Option<TaxSystem> {
-        let brackets = vec![
-            country_tax_system(&self.country),
-            self.state.tax_system(),
-            city_tax_system(self.city.as_str()),
+    pub fn new(country: CountryCode, state: State, city: impl Into<String>) -> Self {
+        Self {
+            country,
+            state,
+            city: city.into(),
+            extra_tax_systems: vec![],
+            payroll_taxes: false,
+            ignore_city_tax: false,
+            work_state: None,
+            standard_deductions: false,
+            social_contribution: false,
+            bracket_index: None,
+            cached_merged_tax_bracket: RefCell::new(None),
+        }
+    }
+
+    pub fn with_payroll_taxes(mut self) -> Self {
+        self.payroll_taxes = true;
+        self.cached_merged_tax_bracket = RefCell::new(None);
+        self
+    }
+
+    pub fn without_city_tax(mut self) -> Self {
+        self.ignore_city_tax = true;
+        self.cached_merged_tax_bracket = RefCell::new(None);
+        self
+    }
+
+    /// Working in `state` while living here, which is taxed by both unless they have a reciprocity
+    /// agreement. Credits for the taxes paid to the other state aren't modeled.
+    pub fn with_work_state(mut self, state: State) -> Self {
+        self.work_state = Some(state);
+        self.cached_merged_tax_bracket = RefCell::new(None);
+        self
+    }
+
+    /// Taking each jurisdiction's own standard deduction off the income before its brackets, so
+    /// the country's and the state's can differ.
+    pub fn with_standard_deductions(mut self) -> Self {
+        self.standard_deductions = true;
+        self.cached_merged_tax_bracket = RefCell::new(None);
+        self
+    }
+
+    /// Charging the country's flat social contribution on top of its income tax, where it has one.
+    pub fn with_social_contribution(mut self) -> Self {
+        self.social_contribution = true;
+        self.cached_merged_tax_bracket = RefCell::new(None);
+        self
+    }
+
+    /// Scaling the separators of every system by an inflation `factor`, to approximate a later
+    /// year than the one the brackets are from.
+    pub fn with_bracket_index(mut self, factor: UR64) -> Self {
+        self.bracket_index = Some(factor);
+        self.cached_merged_tax_bracket = RefCell::new(None);
+        self
+    }
+
+    /// The system with its brackets indexed, if they're indexed at all.
+    fn indexed(&self, system: Option<TaxSystem>) -> Option<TaxSystem> {
+        match self.bracket_index {
+            Some(factor) => system.map(|system| system.indexed(factor)),
+            None => system,
+        }
+    }
+
+    /// The state's taxes, after its standard deduction if it's taken.
+    fn state_tax_system(&self, state: State) -> Option<TaxSystem> {
+        let system = state.tax_system()?;
+        if self.standard_deductions {
+            Some(system.with_deduction(|status| state.standard_deduction(status)))
+        } else {
+            Some(system)
+        }
+    }
+
+    /// The taxes of the state worked in, as a non-resident.
+    fn work_state_tax_system(&self) -> Option<TaxSystem> {
+        let work_state = self.work_state.filter(|state| *state != self.state)?;
+        if self.state.has_reciprocity_with(work_state) {
+            log::info!(
+                "{} and {} have reciprocity, so only {} taxes the income.",
+                self.state,
+                work_state,
+                self.state
+            );
+            return None;
+        }
+        self.state_tax_system(work_state)
+    }
+
+    fn payroll_tax_system(&self) -> Option<TaxSystem> {
+        if self.payroll_taxes {
+            self.indexed(country_payroll_tax_system(&self.country))
+        } else {
+            None
+        }
+    }
+
+    pub fn with_extra_tax_system(mut self, system: TaxSystem) -> Self {
+        self.extra_tax_systems.push(system);
+        self.cached_merged_tax_bracket = RefCell::new(None);
+        self
+    }
+
+    /// Whether anything is charged beyond where the location is, from extra systems or from
+    /// working in another state.
+    pub fn has_extra_taxes(&self) -> bool {
+        !self.extra_tax_systems.is_empty() || self.work_state.filter(|s| *s != self.state).is_some()
+    }
+
+    /// The country's taxes, with the income tax bands replaced where the state sets its own. The
+    /// standard deduction only comes off the income tax, not the social contribution.
+    fn national_tax_system(&self) -> Option<TaxSystem> {
+        let income_tax = self
+            .state
+            .income_tax_system()
+            .unwrap_or_else(|| country_income_tax_system(&self.country));
+        let income_tax = if self.standard_deductions {
+            income_tax.with_deduction(|status| country_standard_deduction(&self.country, status))
+        } else {
+            income_tax
+        };
+        let national = if self.social_contribution {
+            merge_social_contribution(&self.country, income_tax)
+        } else {
+            income_tax
+        };
+        self.indexed(Some(national))
+    }
+
+    /// Everything below the country level, which applies regardless of the country's system.
+    fn local_tax_systems(&self) -> Vec<Option<TaxSystem>> {
+        self.labelled_local_tax_systems()
+            .into_iter()
+            .map(|(_, system)| system)
+            .collect()
+    }
+
+    /// The local systems, labelled by where they're from.
+    fn labelled_local_tax_systems(&self) -> Vec<(String, Option<TaxSystem>)> {
+        let registered = LOCATION_REGISTRY
+            .get()
+            .and_then(|registry| registry.get(self.country, self.state, &self.city));
+        let city = if self.ignore_city_tax {
+            None
+        } else if let Some(registered) = registered {
+            Some(registered.clone())
+        } else {
+            city_tax_system(self.city.as_str())
+        };
+        let mut brackets = vec![
+            (self.state.to_string(), self.state_tax_system(self.state)),
+            (
+                format!("{} (worked in)", self.work_state.unwrap_or(self.state)),
+                self.work_state_tax_system(),
+            ),
+            (self.city.clone(), city),
         ];
+        brackets.extend(
+            self.extra_tax_systems
+                .iter()
+                .enumerate()
+                .map(|(i, system)| (format!("extra #{}", i + 1), Some(system.clone()))),
+        );
+        brackets
+            .into_iter()
+            .map(|(label, system)| (label, self.indexed(system)))
+            .collect()
+    }
 
-        let mut merged = None;
-        for (i, brackets) in brackets.into_iter().enumerate() {
-            log::debug!("Merging bracket {}", i);
-            if let Some(brackets) = brackets {
-                if let Some(merged_brackets) = merged {
-                    merged = Some(TaxSystem::merge(merged_brackets, brackets));
-                } else {
-                    merged = Some(brackets);
-                }
-            }
+    /// Every system that `tax_system` merges, labelled by where it's from.
+    pub fn labelled_tax_systems(&self) -> Vec<(String, TaxSystem)> {
+        let mut brackets = vec![
+            (self.country.alpha3().to_owned(), self.national_tax_system()),
+            (
+                format!("{} (payroll)", self.country.alpha3()),
+                self.payroll_tax_system(),
+            ),
+        ];
+        brackets.extend(self.labelled_local_tax_systems());
+        brackets
+            .into_iter()
+            .filter_map(|(label, system)| Some((label, system?)))
+            .collect()
+    }
+
+    /// The merged system of every jurisdiction the location falls under.
+    pub fn tax_system(&self) -> Option<TaxSystem> {
+        if let Some(cached) = self.cached_merged_tax_bracket.borrow().as_ref() {
+            return Some(cached.clone());
         }
-        log::debug!("Final brackets: {:?}", merged);
+
+        let mut brackets = vec![self.national_tax_system(), self.payroll_tax_system()];
+        brackets.extend(self.local_tax_systems());
+        let merged = merge_tax_systems(brackets);
+        // Locations without any taxes are cheap to recompute, so only successful merges are cached.
+        *self.cached_merged_tax_bracket.borrow_mut() = merged.clone();
         merged
     }
 
-    pub fn calc_taxes(&self, gross: &BigUR, status: MaritalStatus) -> BigUR {
-        self.tax_system()
-            .map_or_else(|| BigUR::zero(), |sys| sys.calc_taxes(gross, status))
+    /// The AMT schedule merged with the payroll and local taxes, which is what's owed when the AMT
+    /// wins on an AMT income equal to the gross.
+    fn alternative_tax_system(&self) -> Option<TaxSystem> {
+        let alternative = self.indexed(country_alternative_tax_system(&self.country))?;
+        let mut brackets = vec![Some(alternative), self.payroll_tax_system()];
+        brackets.extend(self.local_tax_systems());
+        merge_tax_systems(brackets)
+    }
+
+    /// The local taxes on `gross`, which the SALT deduction is taken from.
+    fn calc_local_taxes(&self, gross: &BigUR, status: MaritalStatus) -> BigUR {
+        merge_tax_systems(self.local_tax_systems())
+            .map_or_else(BigUR::zero, |sys| sys.calc_taxes(gross, status))
+    }
+
+    /// The country's regular taxes on `gross`, after deducting the local taxes up to the cap when
+    /// `salt` is on.
+    fn calc_national_taxes(&self, gross: &BigUR, status: MaritalStatus, salt: bool) -> BigUR {
+        let deduction = if salt {
+            let local = self.calc_local_taxes(gross, status);
+            country_local_tax_deduction_cap(&self.country, status)
+                .map_or_else(BigUR::zero, |cap| std::cmp::min(local, cap))
+        } else {
+            BigUR::zero()
+        };
+        let taxable = if *gross > deduction {
+            gross.clone() - deduction
+        } else {
+            BigUR::zero()
+        };
+        self.national_tax_system()
+            .map_or_else(BigUR::zero, |sys| sys.calc_taxes(&taxable, status))
     }
 
-    pub fn calc_net(&self, gross: &BigUR, status: MaritalStatus) -> BigUR {
-        self.tax_system()
-            .map_or_else(|| gross.clone(), |sys| sys.calc_net(gross, status))
+    /// The AMT on `amt_income`, which is nothing outside of the countries that have one. Its
+    /// schedule never takes off the standard or SALT deductions, so they're already added back.
+    fn calc_alternative_minimum_taxes(&self, amt_income: &BigUR, status: MaritalStatus) -> BigUR {
+        self.indexed(country_alternative_tax_system(&self.country))
+            .map_or_else(BigUR::zero, |sys| sys.calc_taxes(amt_income, status))
     }
 
-    pub fn calc_gross(&self, net: &BigUR, status: MaritalStatus) -> BigUR {
-        self.tax_system()
-            .map_or_else(|| net.clone(), |sys| sys.calc_gross(net, status))
+    /// The taxes on `gross`, where the country charges the larger of its regular taxes and the AMT
+    /// on `amt_income`, if there's one. The payroll and local taxes are owed on top either way.
+    pub fn calc_taxes_with_amt_income(
+        &self,
+        gross: &BigUR,
+        amt_income: Option<&BigUR>,
+        status: MaritalStatus,
+        salt: bool,
+    ) -> BigUR {
+        if amt_income.is_none() && !salt {
+            return self
+                .tax_system()
+                .map_or_else(BigUR::zero, |sys| sys.calc_taxes(gross, status));
+        }
+        let regular = self.calc_national_taxes(gross, status, salt);
+        let national = match amt_income {
+            Some(amt_income) => std::cmp::max(
+                regular,
+                self.calc_alternative_minimum_taxes(amt_income, status),
+            ),
+            None => regular,
+        };
+        // Payroll taxes are on the wages themselves, so the deduction doesn't lower them.
+        let payroll = self
+            .payroll_tax_system()
+            .map_or_else(BigUR::zero, |sys| sys.calc_taxes(gross, status));
+        national + payroll + self.calc_local_taxes(gross, status)
     }
 
-    pub fn get_living_costs_factor(&self) -> BigUR {
+    /// The deduction makes the taxes depend on themselves, so there's no bracket walk to invert.
+    /// Instead, the gross is bisected to the cent between the net itself and the gross needed
+    /// without the deduction, since deducting can only ever lower the taxes.
+    fn calc_gross_deducting_local(&self, net: &BigUR, status: MaritalStatus) -> BigUR {
+        let cent = BigUR::new(BigUint::one(), BigUint::from(100u8));
+        let two = BigUint::from(2u8);
+        let mut low = net.clone();
+        let mut high = self
+            .tax_system()
+            .map_or_else(|| net.clone(), |sys| sys.calc_gross(net, status));
+        while high.clone() - low.clone() > cent {
+            let mid = (low.clone() + high.clone()) / two.clone();
+            if mid.clone() - self.calc_taxes(&mid, status, false, true) < *net {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        high
+    }
+
+    /// The taxes on `gross`, with the AMT taken on the same income when `amt` is on.
+    pub fn calc_taxes(&self, gross: &BigUR, status: MaritalStatus, amt: bool, salt: bool) -> BigUR {
+        self.calc_taxes_with_amt_income(gross, Some(gross).filter(|_| amt), status, salt)
+    }
+
+    /// Only the schedules made for capital gains apply, so gains are untaxed everywhere else.
+    pub fn calc_capital_gains_taxes(&self, gains: &BigUR, status: MaritalStatus) -> BigUR {
+        self.state
+            .capital_gains_tax_system()
+            .map_or_else(BigUR::zero, |sys| sys.calc_taxes(gains, status))
+    }
+
+    pub fn calc_net_with_amt_income(
+        &self,
+        gross: &BigUR,
+        amt_income: Option<&BigUR>,
+        status: MaritalStatus,
+        salt: bool,
+    ) -> BigUR {
+        if amt_income.is_none() && !salt {
+            return self
+                .tax_system()
+                .map_or_else(|| gross.clone(), |sys| sys.calc_net(gross, status));
+        }
+        gross.clone() - self.calc_taxes_with_amt_income(gross, amt_income, status, salt)
+    }
+
+    /// Since net income only ever increases with gross income, the gross needed to reach `net` is
+    /// the larger of the ones needed with and without the AMT. With the AMT taken on the gross
+    /// itself, it winning means owing the merged alternative schedule.
+    pub fn calc_gross(&self, net: &BigUR, status: MaritalStatus, amt: bool, salt: bool) -> BigUR {
+        let regular = if salt {
+            self.calc_gross_deducting_local(net, status)
+        } else {
+            self.tax_system()
+                .map_or_else(|| net.clone(), |sys| sys.calc_gross(net, status))
+        };
+        // The alternative minimum tax doesn't allow the deduction.
+        let alternative = self
+            .alternative_tax_system()
+            .filter(|_| amt)
+            .map(|sys| sys.calc_gross(net, status));
+        std::iter::once(regular)
+            .chain(alternative)
+            .max()
+            .unwrap_or_else(|| net.clone())
+    }
+
+    pub fn country_name(&self) -> &'static str {
+        self.country.name()
+    }
+
+    pub fn state_name(&self) -> &'static str {
+        self.state.display_name()
+    }
+
+    /// The human-friendly counterpart of `Display`, like "SF, California, United States of America".
+    pub fn display_name(&self) -> String {
+        format!(
+            "{}, {}, {}",
+            self.city,
+            self.state_name(),
+            self.country_name()
+        )
+    }
+
+    pub fn sales_tax_rate(&self) -> UR64 {
+        country_sales_tax_rate(&self.country) + self.state.sales_tax_rate()
+    }
+
+    /// How much of `contribution` to a health savings account goes in before taxes, which is none
+    /// outside of the countries that have them. States that tax it anyway, like California, aren't
+    /// modeled.
+    pub fn hsa_pre_tax_amount(&self, contribution: &BigUR, coverage: HsaCoverage) -> BigUR {
+        country_hsa_limit(&self.country, coverage).map_or_else(BigUR::zero, |limit| {
+            std::cmp::min(contribution.clone(), limit)
+        })
+    }
+
+    /// The credit for `expenses` on childcare for `dependents`, which is none outside of the
+    /// countries that have one. It's taken off the taxes after the brackets, so it can't take them
+    /// below zero.
+    pub fn dependent_care_credit(&self, expenses: &BigUR, dependents: u32) -> BigUR {
+        country_dependent_care_credit_rate(&self.country).map_or_else(BigUR::zero, |rate| {
+            let cap = country_dependent_care_expense_cap(&self.country, dependents);
+            std::cmp::min(expenses.clone(), cap) * cast_ratio(rate)
+        })
+    }
+
+    /// The deduction on `qbi` of qualified business income, when the taxable income before it is
+    /// `income`. It's 20% of the business income, up to 20% of the taxable income. The business is
+    /// taken to be a specified service one, so above the threshold the deduction phases out rather
+    /// than being limited by wages. It's taken off before every bracket, including the states'.
+    pub fn qbi_deduction(&self, qbi: &BigUR, income: &BigUR, status: MaritalStatus) -> BigUR {
+        let (threshold, range) = match country_qbi_phaseout(&self.country, status) {
+            Some(phaseout) => phaseout,
+            None => return BigUR::zero(),
+        };
+        let full = std::cmp::min(qbi.clone(), income.clone()) * BigUR::new(1u8.into(), 5u8.into());
+        let end = threshold.clone() + range.clone();
+        if *income <= threshold {
+            full
+        } else if *income >= end {
+            BigUR::zero()
+        } else {
+            full * (end - income.clone()) / range
+        }
+    }
+
+    /// Only the country's withholding, so the states' own supplemental rates are left out.
+    pub fn supplemental_withholding_rate(&self) -> Option<UR64> {
+        country_supplemental_withholding_rate(&self.country)
+    }
+
+    pub fn currency(&self) -> CurrencyCode {
+        CurrencyCode::of_country(&self.country)
+    }
+
+    pub fn get_living_costs_factor(&self) -> Result<BigUR, String> {
         // TODO Look up this cost factor online at some point. Like here, for example: https://www.nerdwallet.com/cost-of-living-calculator/compare/new-york-manhattan-ny-vs-san-francisco-ca
         // These factors are relative to NYC
         let factor = match (self.country, self.state, self.city.as_str()) {
             (CountryCode::USA, State::CA, "SF") | (CountryCode::USA, State::CA, "San Francisco") => UR64::new(39_842, 50_000),
             (CountryCode::USA, State::TX, "AUS") | (CountryCode::USA, State::TX, "Austin") => UR64::new(19_847, 50_000),
+            (CountryCode::USA, State::OR, "PDX") | (CountryCode::USA, State::OR, "Portland") => UR64::new(26_073, 50_000),
+            (CountryCode::USA, State::WA, "SEA") | (CountryCode::USA, State::WA, "Seattle") => UR64::new(36_871, 50_000),
+            (CountryCode::USA, State::IL, "CHI") | (CountryCode::USA, State::IL, "Chicago") => UR64::new(27_650, 50_000),
+            (CountryCode::USA, State::IN, "IND") | (CountryCode::USA, State::IN, "Indianapolis") => UR64::new(19_120, 50_000),
+            (CountryCode::USA, State::MI, "DTW") | (CountryCode::USA, State::MI, "Detroit") => UR64::new(18_440, 50_000),
+            (CountryCode::GBR, State::ENG, "London") => UR64::new(37_500, 50_000),
+            (CountryCode::GBR, State::SCT, "Edinburgh") => UR64::new(25_260, 50_000),
             (CountryCode::USA, State::TX, "NYC") | (CountryCode::USA, State::TX, "New York") | (CountryCode::USA, State::TX, "NY") => UR64::one(),
-            _ => unimplemented!("Living costs not implemented for {:?}. For now, it only works when used with the '--usage post_tax' cmd flag.", self),
+            _ => return Err(format!("Living costs not implemented for {}. For now, it only works when used with the '--usage post_tax' cmd flag.", self)),
         };
-        cast_ratio(factor)
+        Ok(cast_ratio(factor))
+    }
+
+    /// Relative to NYC like the blended factor, for the cities where it's known how much each
+    /// category differs.
+    fn get_living_costs_breakdown(&self) -> Option<HashMap<ExpenseCategory, UR64>> {
+        let (housing, food, transport) = match (self.country, self.state, self.city.as_str()) {
+            (CountryCode::USA, State::CA, "SF")
+            | (CountryCode::USA, State::CA, "San Francisco") => {
+                (UR64::new(83, 100), UR64::new(95, 100), UR64::new(105, 100))
+            }
+            (CountryCode::USA, State::TX, "AUS") | (CountryCode::USA, State::TX, "Austin") => {
+                (UR64::new(32, 100), UR64::new(72, 100), UR64::new(80, 100))
+            }
+            (CountryCode::USA, State::OR, "PDX") | (CountryCode::USA, State::OR, "Portland") => {
+                (UR64::new(42, 100), UR64::new(82, 100), UR64::new(86, 100))
+            }
+            (CountryCode::USA, State::WA, "SEA") | (CountryCode::USA, State::WA, "Seattle") => {
+                (UR64::new(58, 100), UR64::new(88, 100), UR64::new(95, 100))
+            }
+            (CountryCode::USA, State::TX, "NYC")
+            | (CountryCode::USA, State::TX, "New York")
+            | (CountryCode::USA, State::TX, "NY") => (UR64::one(), UR64::one(), UR64::one()),
+            _ => return None,
+        };
+        Some(hashmap! {
+            ExpenseCategory::Housing => housing,
+            ExpenseCategory::Food => food,
+            ExpenseCategory::Transport => transport,
+        })
+    }
+
+    /// Falls back to the blended factor where there's no breakdown.
+    pub fn get_category_living_costs_factor(
+        &self,
+        category: ExpenseCategory,
+    ) -> Result<BigUR, String> {
+        match self
+            .get_living_costs_breakdown()
+            .and_then(|breakdown| breakdown.get(&category).copied())
+        {
+            Some(factor) => Ok(cast_ratio(factor)),
+            None => self.get_living_costs_factor(),
+        }
     }
 }
 
-impl std::str::FromStr for Location {
-    type Err = String;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<_> = s.split("///").collect();
+/// Only where the location is counts, so the cache and the options like `--fica` are ignored.
+impl PartialEq for Location {
+    fn eq(&self, other: &Self) -> bool {
+        self.country == other.country && self.state == other.state && self.city == other.city
+    }
+}
+
+impl Eq for Location {}
+
+/// Hashes the same fields that equality compares.
+impl std::hash::Hash for Location {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.country.hash(state);
+        self.state.hash(state);
+        self.city.hash(state);
+    }
+}
+
+/// What separates the parts of a location, unless `EQUINC_LOCATION_DELIMITER` sets another.
+const DEFAULT_LOCATION_DELIMITER: &str = "///";
+
+fn location_delimiter() -> String {
+    std::env::var("EQUINC_LOCATION_DELIMITER")
+        .ok()
+        .filter(|delimiter| !delimiter.is_empty())
+        .unwrap_or_else(|| DEFAULT_LOCATION_DELIMITER.to_owned())
+}
+
+/// Only where the location is gets written, like its equality, and always with the default
+/// delimiter so that it reads back the same whatever `EQUINC_LOCATION_DELIMITER` is set to.
+impl Serialize for Location {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!(
+            "{}{delimiter}{}{delimiter}{}",
+            self.country.alpha3(),
+            self.state,
+            self.city,
+            delimiter = DEFAULT_LOCATION_DELIMITER
+        ))
+    }
+}
+
+impl<'de> Deserialize<'de> for Location {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let location = String::deserialize(deserializer)?;
+        Location::parse_with_delimiter(&location, DEFAULT_LOCATION_DELIMITER)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Prints the location in the same form that it's parsed from.
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let delimiter = location_delimiter();
+        write!(
+            f,
+            "{}{}{}{}{}",
+            self.country.alpha3(),
+            delimiter,
+            self.state,
+            delimiter,
+            self.city
+        )
+    }
+}
+
+impl Location {
+    /// Parses a location like `"Country///State///City"`, with `delimiter` in place of `///`.
+    pub fn parse_with_delimiter(s: &str, delimiter: &str) -> Result<Self, String> {
+        let parts: Vec<_> = s.split(delimiter).collect();
+        if parts.len() != 3 {
+            return Err(format!(
+                "Expected a location like \"Country{0}State{0}City\", not {1:?}",
+                delimiter, s
+            ));
+        }
         let country = match parts[0] {
             "USA" | "United States" | "America" | "US" => Ok(CountryCode::USA),
-            _ => Err(format!("Could not parse country {:?}", parts[0])),
+            "GBR" | "United Kingdom" | "UK" | "GB" => Ok(CountryCode::GBR),
+            _ => {
+                let supported: Vec<_> = supported_countries()
+                    .iter()
+                    .map(|country| country.alpha3())
+                    .collect();
+                Err(format!(
+                    "Could not parse country {:?}, expected one of {}",
+                    parts[0],
+                    supported.join(", ")
+                ))
+            }
         }?;
-        Ok(Location {
-            country: country,
-            state: parts[1].parse()?,
-            city: parts[2].to_owned(),
-            cached_merged_tax_bracket: RefCell::new(None),
-        })
+        Ok(Location::new(country, parts[1].parse()?, parts[2]))
+    }
+}
+
+impl std::str::FromStr for Location {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Location::parse_with_delimiter(s, &location_delimiter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn dollars(amount: u64) -> BigUR {
+        BigUR::from_integer(amount.into())
+    }
+
+    fn austin() -> Location {
+        Location::new(CountryCode::USA, State::TX, "Austin")
+    }
+
+    #[test]
+    fn amt_is_owed_on_the_income_with_deductions_added_back() {
+        // A 32,000 QBI deduction brings 160,000 down to 128,000, and the standard deduction to
+        // 115,600, which the regular brackets tax at 21,823.50. The AMT is 26% of the 87,100
+        // left of the whole 160,000 above the 72,900 exemption.
+        let taxable = dollars(128_000);
+        let amt_income = dollars(160_000);
+        for location in [austin(), Location::new(CountryCode::USA, State::CA, "SF")] {
+            let location = location.with_standard_deductions();
+            let status = MaritalStatus::Single;
+            let regular = location.calc_taxes_with_amt_income(&taxable, None, status, false);
+            let with_amt =
+                location.calc_taxes_with_amt_income(&taxable, Some(&amt_income), status, false);
+            assert_eq!(
+                with_amt.clone() - regular,
+                BigUR::new(1645u32.into(), 2u32.into())
+            );
+            assert_eq!(
+                location.calc_net_with_amt_income(&taxable, Some(&amt_income), status, false),
+                taxable.clone() - with_amt
+            );
+        }
+        let with_amt = austin()
+            .with_standard_deductions()
+            .calc_taxes_with_amt_income(&taxable, Some(&amt_income), MaritalStatus::Single, false);
+        assert_eq!(with_amt, dollars(22_646));
+    }
+
+    #[test]
+    fn amt_is_not_owed_when_the_regular_tax_is_higher() {
+        let income = dollars(300_000);
+        let location = austin();
+        assert_eq!(
+            location.calc_taxes(&income, MaritalStatus::Single, true, false),
+            location.calc_taxes(&income, MaritalStatus::Single, false, false)
+        );
+    }
+
+    #[test]
+    fn location_built_with_new_computes_taxes() {
+        let income = dollars(100_000);
+        let built = Location::new(CountryCode::USA, State::CA, "SF");
+        let parsed = Location::parse_with_delimiter("USA///CA///SF", "///").unwrap();
+        let taxes = built.calc_taxes(&income, MaritalStatus::Single, false, false);
+        assert!(taxes > BigUR::zero());
+        assert_eq!(
+            taxes,
+            parsed.calc_taxes(&income, MaritalStatus::Single, false, false)
+        );
+    }
+
+    #[test]
+    fn locations_are_equal_whether_or_not_their_taxes_are_cached() {
+        let cached = austin();
+        cached.tax_system();
+        assert_eq!(
+            cached,
+            Location::parse_with_delimiter("USA///TX///Austin", "///").unwrap()
+        );
+        assert_ne!(cached, Location::new(CountryCode::USA, State::TX, "Dallas"));
+    }
+
+    #[test]
+    fn display_round_trips_through_from_str() {
+        let location = Location::new(CountryCode::USA, State::CA, "San Francisco");
+        let parsed: Location = location.to_string().parse().unwrap();
+        assert_eq!(parsed, location);
+        assert_eq!(parsed.city, "San Francisco");
+    }
+
+    static COUNTED_BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+    fn build_counted_tax_system() -> TaxSystem {
+        COUNTED_BUILDS.fetch_add(1, Ordering::SeqCst);
+        build_usa_tax_system()
+    }
+
+    cached_tax_system!(counted_tax_system, build_counted_tax_system);
+
+    #[test]
+    fn cached_tax_systems_are_built_once() {
+        let systems: Vec<_> = (0..3).map(|_| counted_tax_system()).collect();
+        assert_eq!(COUNTED_BUILDS.load(Ordering::SeqCst), 1);
+        assert!(systems.iter().all(|system| *system == usa_tax_system()));
+    }
+
+    #[test]
+    fn salt_deduction_lowers_the_federal_tax_up_to_the_cap() {
+        let income = dollars(200_000);
+        let sf = Location::new(CountryCode::USA, State::CA, "SF");
+        let without = sf.calc_taxes(&income, MaritalStatus::Single, false, false);
+        let with = sf.calc_taxes(&income, MaritalStatus::Single, false, true);
+        // California takes well over the $10,000 cap, and all of it comes off the 32% band.
+        assert_eq!(without - with, dollars(3_200));
+        // Texas has no income tax to deduct.
+        assert_eq!(
+            austin().calc_taxes(&income, MaritalStatus::Single, false, true),
+            austin().calc_taxes(&income, MaritalStatus::Single, false, false)
+        );
+    }
+
+    #[test]
+    fn built_in_tax_systems_pass_the_check() {
+        assert_eq!(check_built_in_tax_systems(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn basis_points_match_the_built_in_california_rate() {
+        let ca = ca_tax_system();
+        let rate = ca
+            .brackets(MaritalStatus::Single)
+            .unwrap()
+            .marginal_rate(&dollars(100_000));
+        assert_eq!(parse_rate("1023bps"), Ok(rate));
+        assert_eq!(rate, UR64::new(1_023, 10_000));
+    }
+
+    #[test]
+    fn states_and_locations_have_human_friendly_names() {
+        assert_eq!(State::CA.display_name(), "California");
+        assert_eq!(State::TX.display_name(), "Texas");
+        assert_eq!(
+            austin().display_name(),
+            "Austin, Texas, United States of America"
+        );
+    }
+
+    #[test]
+    fn additional_medicare_tax_starts_at_the_threshold_of_the_status() {
+        let payroll = usa_payroll_tax_system();
+        let income = dollars(220_000);
+        let single = payroll.calc_taxes(&income, MaritalStatus::Single);
+        let joint = payroll.calc_taxes(&income, MaritalStatus::Joint);
+        // Single filers are $20,000 over their $200,000 threshold, while joint filers have until
+        // $250,000.
+        assert_eq!(single - joint.clone(), dollars(180));
+        let social_security = dollars(137_700) * BigUR::new(62u8.into(), 1_000u16.into());
+        let medicare = income * BigUR::new(145u8.into(), 10_000u16.into());
+        assert_eq!(joint, social_security + medicare);
+    }
+
+    #[test]
+    fn merged_sf_top_rate_sums_the_federal_state_and_city_ones() {
+        let top = dollars(10_000_000);
+        let top_rate = |system: Option<TaxSystem>| {
+            system
+                .unwrap()
+                .brackets(MaritalStatus::Single)
+                .unwrap()
+                .marginal_rate(&top)
+        };
+        let sf = Location::new(CountryCode::USA, State::CA, "SF");
+        let summed = top_rate(Some(usa_tax_system()))
+            + top_rate(Some(ca_tax_system()))
+            + top_rate(city_tax_system("SF"));
+        assert_eq!(top_rate(sf.tax_system()), summed);
+        assert_eq!(
+            summed,
+            UR64::new(37, 100) + UR64::new(1_463, 10_000) + UR64::new(15, 1_000)
+        );
+    }
+
+    #[test]
+    fn ignoring_the_city_tax_takes_off_the_sf_tax() {
+        let income = dollars(100_000);
+        let sf = Location::new(CountryCode::USA, State::CA, "SF");
+        let with_city = sf.calc_taxes(&income, MaritalStatus::Single, false, false);
+        let without_city =
+            sf.without_city_tax()
+                .calc_taxes(&income, MaritalStatus::Single, false, false);
+        // SF's flat 1.5%.
+        assert_eq!(with_city - without_city, dollars(1_500));
+    }
+
+    #[test]
+    fn social_contribution_adds_a_flat_share_of_the_income() {
+        let london = Location::new(CountryCode::GBR, State::ENG, "London");
+        let contributing = london.clone().with_social_contribution();
+        for income in [10_000, 50_000, 200_000] {
+            let income = dollars(income);
+            let added = contributing.calc_taxes(&income, MaritalStatus::Single, false, false)
+                - london.calc_taxes(&income, MaritalStatus::Single, false, false);
+            assert_eq!(added, income * BigUR::new(2u8.into(), 100u8.into()));
+        }
+        // Countries without one are left as they were.
+        assert_eq!(
+            austin().with_social_contribution().calc_taxes(
+                &dollars(50_000),
+                MaritalStatus::Single,
+                false,
+                false
+            ),
+            austin().calc_taxes(&dollars(50_000), MaritalStatus::Single, false, false)
+        );
+    }
+
+    #[test]
+    fn washington_taxes_gains_above_the_threshold_but_not_wages() {
+        let seattle = Location::new(CountryCode::USA, State::WA, "Seattle");
+        let amount = dollars(300_000);
+        // 7% of the $50,000 above the $250,000 threshold.
+        assert_eq!(
+            seattle.calc_capital_gains_taxes(&amount, MaritalStatus::Single),
+            dollars(3_500)
+        );
+        assert_eq!(
+            seattle.calc_capital_gains_taxes(&dollars(250_000), MaritalStatus::Single),
+            BigUR::zero()
+        );
+        // Only the federal taxes are owed on wages, the same as in Texas.
+        assert_eq!(
+            seattle.calc_taxes(&amount, MaritalStatus::Single, false, false),
+            austin().calc_taxes(&amount, MaritalStatus::Single, false, false)
+        );
+    }
+
+    #[test]
+    fn scottish_bands_tax_a_higher_earner_more_than_the_rest_of_the_uk() {
+        let taxes = |state, city| {
+            Location::new(CountryCode::GBR, state, city).calc_taxes(
+                &dollars(50_000),
+                MaritalStatus::Single,
+                false,
+                false,
+            )
+        };
+        // 20% of the 37,500 above the personal allowance.
+        assert_eq!(taxes(State::ENG, "London"), dollars(7_500));
+        // 19% of 2,085, 20% of 10,573, 21% of 18,272 and 41% of the 6,570 above 43,430.
+        assert_eq!(
+            taxes(State::SCT, "Edinburgh"),
+            BigUR::new(904_157u32.into(), 100u8.into())
+        );
+    }
+
+    #[test]
+    fn every_built_in_city_has_a_living_costs_factor() {
+        let cities = [
+            Location::new(CountryCode::USA, State::IL, "Chicago"),
+            Location::new(CountryCode::USA, State::IN, "Indianapolis"),
+            Location::new(CountryCode::USA, State::MI, "Detroit"),
+            Location::new(CountryCode::GBR, State::ENG, "London"),
+            Location::new(CountryCode::GBR, State::SCT, "Edinburgh"),
+        ];
+        for city in cities.iter() {
+            assert!(city.get_living_costs_factor().is_ok(), "{}", city);
+        }
+        let unknown = Location::new(CountryCode::USA, State::TX, "Dallas");
+        assert!(unknown
+            .get_living_costs_factor()
+            .unwrap_err()
+            .contains("Dallas"));
+    }
+
+    #[test]
+    fn registered_city_system_is_charged_on_top_of_the_state_and_country() {
+        // The only test to register anything, and for a city nothing else uses.
+        let mut registry = LocationRegistry::default();
+        registry.insert(
+            CountryCode::USA,
+            State::TX,
+            "Registered Springs",
+            TaxSystem::flat(UR64::new(1, 100)),
+        );
+        set_location_registry(registry);
+        let registered = Location::new(CountryCode::USA, State::TX, "Registered Springs");
+        let income = dollars(100_000);
+        let added = registered.calc_taxes(&income, MaritalStatus::Single, false, false)
+            - austin().calc_taxes(&income, MaritalStatus::Single, false, false);
+        assert_eq!(added, dollars(1_000));
+    }
+
+    #[test]
+    fn usa_and_california_flats_start_at_zero_and_never_decrease() {
+        let systems = [
+            country_tax_system(&CountryCode::USA).unwrap(),
+            State::CA.tax_system().unwrap(),
+        ];
+        for system in systems.iter() {
+            assert_eq!(system.problems(), Vec::<String>::new());
+        }
+    }
+
+    #[test]
+    fn locations_hash_the_same_whether_or_not_their_taxes_are_cached() {
+        use std::hash::{Hash, Hasher};
+        let hash = |location: &Location| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            location.hash(&mut hasher);
+            hasher.finish()
+        };
+        let cached = austin();
+        cached.tax_system();
+        assert_eq!(hash(&cached), hash(&austin()));
+    }
+
+    #[test]
+    fn added_city_table_row_is_found_by_any_of_its_names() {
+        let mut table = CITY_TABLE.to_vec();
+        table.push(CityData {
+            names: &["Springfield", "SPI"],
+            flat_rate: Some((1, 100)),
+        });
+        let rates = index_city_rates(&table);
+        for name in ["Springfield", " spi "] {
+            assert_eq!(
+                rates.get(&normalize_city_name(name)),
+                Some(&Some(UR64::new(1, 100))),
+                "{}",
+                name
+            );
+        }
+        // The built-in rows are still there.
+        assert_eq!(
+            rates.get(&normalize_city_name("San Francisco")),
+            Some(&Some(UR64::new(15, 1_000)))
+        );
+        assert_eq!(rates.get(&normalize_city_name("Austin")), Some(&None));
+    }
+
+    #[test]
+    fn reciprocal_states_only_tax_the_resident_state() {
+        let indianapolis = Location::new(CountryCode::USA, State::IN, "Indianapolis");
+        let income = dollars(100_000);
+        let taxes =
+            |location: Location| location.calc_taxes(&income, MaritalStatus::Single, false, false);
+        let home_only = taxes(indianapolis.clone());
+        // Indiana and Michigan have an agreement, so working in Michigan changes nothing.
+        assert_eq!(
+            taxes(indianapolis.clone().with_work_state(State::MI)),
+            home_only
+        );
+        // Illinois has none with Indiana, so its flat 4.95% is owed on top.
+        assert_eq!(
+            taxes(indianapolis.with_work_state(State::IL)) - home_only,
+            dollars(4_950)
+        );
+    }
+
+    #[test]
+    fn locations_parse_with_a_custom_delimiter() {
+        assert_eq!(
+            Location::parse_with_delimiter("USA|CA|SF", "|"),
+            Ok(Location::new(CountryCode::USA, State::CA, "SF"))
+        );
+        let err = Location::parse_with_delimiter("USA///CA///SF", "|").unwrap_err();
+        assert!(err.contains("\"Country|State|City\""), "{}", err);
+    }
+
+    #[test]
+    fn california_deduction_comes_off_its_own_brackets_rather_than_the_federal_one() {
+        let income = dollars(100_000);
+        let saved = |location: Location| {
+            location.calc_taxes(&income, MaritalStatus::Single, false, false)
+                - location.with_standard_deductions().calc_taxes(
+                    &income,
+                    MaritalStatus::Single,
+                    false,
+                    false,
+                )
+        };
+        // The federal 12,400 comes off the 24% band.
+        let federal = dollars(2_976);
+        assert_eq!(saved(austin()), federal);
+        // California's 4,601 only comes off its own 10.23% band, and SF has no deduction at all.
+        let sf = Location::new(CountryCode::USA, State::CA, "SF");
+        assert_eq!(
+            saved(sf),
+            federal + dollars(4_601) * BigUR::new(1_023u32.into(), 10_000u32.into())
+        );
+    }
+
+    #[test]
+    fn unknown_country_error_lists_the_supported_ones() {
+        let err = Location::parse_with_delimiter("FRA///ENG///Paris", "///").unwrap_err();
+        assert!(err.contains("USA"), "{}", err);
+        assert_eq!(
+            err,
+            "Could not parse country \"FRA\", expected one of USA, GBR"
+        );
+    }
+
+    #[test]
+    fn unknown_state_error_lists_the_supported_ones() {
+        assert_eq!(
+            "NY".parse::<State>().unwrap_err(),
+            "Could not parse state \"NY\", expected one of CA, TX, OR, WA, IL, IN, MI, ENG, SCT"
+        );
     }
 }
@@ -4,16 +4,91 @@ use fern::{
     log_file, Dispatch, InitError,
 };
 use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+
+/// Same syntax as `RUST_LOG`, e.g. `info,equinc::brackets::walk=off`.
+const LOG_SPEC_VAR: &str = "EQUINC_LOG";
+
+/// How each log line is written.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum LogFormat {
+    /// Human readable, with the level colored.
+    #[default]
+    Text,
+    /// One JSON object a line, with the `timestamp`, `level`, `target` and `message`.
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Failed to understand log format {:?}.", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
 
 struct Cfg {
     level: LevelFilter,
+    format: LogFormat,
     bypass_stdio: bool,
+    target_levels: Vec<(String, LevelFilter)>,
 }
 
 impl Cfg {
+    /// Overrides the levels with the comma separated `level` and `target=level` directives in the
+    /// spec. Unparseable directives are reported and skipped.
+    fn apply_spec(mut self, spec: &str) -> Self {
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (target, level) = match directive.find('=') {
+                Some(idx) => (Some(&directive[..idx]), &directive[idx + 1..]),
+                None => (None, directive),
+            };
+            let level = match level.parse::<LevelFilter>() {
+                Ok(level) => level,
+                Err(_) => {
+                    eprintln!("Ignoring unparseable log directive {:?}.", directive);
+                    continue;
+                }
+            };
+            match target {
+                Some(target) => self.target_levels.push((target.to_owned(), level)),
+                None => self.level = level,
+            }
+        }
+        self
+    }
+
+    fn apply_env(self) -> Self {
+        match std::env::var(LOG_SPEC_VAR) {
+            Ok(spec) => self.apply_spec(spec.as_str()),
+            Err(_) => self,
+        }
+    }
+
+    /// Lets through only what the level and the levels of each target allow.
+    fn filter(self, dispatch: Dispatch) -> Dispatch {
+        self.target_levels
+            .into_iter()
+            .fold(dispatch.level(self.level), |dispatch, (target, level)| {
+                dispatch.level_for(target, level)
+            })
+    }
+
     fn setup_logger(self) -> Result<(), InitError> {
-        let dispatch = Dispatch::new()
-            .format(|out, message, record| {
+        let dispatch = match self.format {
+            LogFormat::Text => Dispatch::new().format(|out, message, record| {
                 let colors = ColoredLevelConfig::new()
                     .trace(Color::BrightBlack)
                     .debug(Color::White)
@@ -27,10 +102,20 @@ impl Cfg {
                     colors.color(record.level()),
                     message
                 ))
-            })
-            .level(self.level)
-            .chain(log_file("output.log")?);
-        let dispatch = if self.bypass_stdio {
+            }),
+            LogFormat::Json => Dispatch::new().format(|out, message, record| {
+                let line = serde_json::json!({
+                    "timestamp": Local::now().to_rfc3339(),
+                    "level": record.level().to_string(),
+                    "target": record.target(),
+                    "message": message.to_string(),
+                });
+                out.finish(format_args!("{}", line))
+            }),
+        };
+        let bypass_stdio = self.bypass_stdio;
+        let dispatch = self.filter(dispatch).chain(log_file("output.log")?);
+        let dispatch = if bypass_stdio {
             dispatch
         } else {
             dispatch.chain(std::io::stdout())
@@ -40,19 +125,58 @@ impl Cfg {
 }
 
 #[cfg(debug_assertions)]
-pub fn setup() -> Result<(), InitError> {
+pub fn setup(format: LogFormat) -> Result<(), InitError> {
     Cfg {
         level: LevelFilter::Debug,
+        format,
         bypass_stdio: false,
+        target_levels: vec![],
     }
+    .apply_env()
     .setup_logger()
 }
 
 #[cfg(not(debug_assertions))]
-pub fn setup() -> Result<(), InitError> {
+pub fn setup(format: LogFormat) -> Result<(), InitError> {
     Cfg {
         level: LevelFilter::Info,
+        format,
         bypass_stdio: true,
+        target_levels: vec![],
     }
+    .apply_env()
     .setup_logger()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::brackets::WALK_LOG_TARGET;
+    use log::{Level, Metadata};
+
+    #[test]
+    fn walk_target_is_filtered_out_while_others_are_kept() {
+        let cfg = Cfg {
+            level: LevelFilter::Debug,
+            format: LogFormat::Text,
+            bypass_stdio: true,
+            target_levels: vec![],
+        }
+        .apply_spec("info,equinc::brackets::walk=off");
+        let (_, logger) = cfg
+            .filter(Dispatch::new())
+            .chain(fern::Output::call(|_| {}))
+            .into_log();
+        let enabled = |target| {
+            logger.enabled(
+                &Metadata::builder()
+                    .level(Level::Info)
+                    .target(target)
+                    .build(),
+            )
+        };
+        assert!(!enabled(WALK_LOG_TARGET));
+        assert!(enabled("equinc::brackets"));
+        assert!(enabled("equinc::loc"));
+    }
+}
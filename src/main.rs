@@ -1,70 +1,808 @@
-use num::bigint::BigUint;
+use chrono::Datelike;
+use currency::Currency;
+use num::{
+    bigint::BigUint,
+    traits::{One, Zero},
+};
+use std::cell::RefCell;
 use structopt::StructOpt;
 
+mod batch;
 mod brackets;
 mod cfg;
 mod citizen;
+mod exchange;
 mod loc;
 mod logger;
+mod report;
+mod scenario;
 mod util;
 
-use cfg::Opts;
+use brackets::{MaritalStatus, TaxSystem};
+use cfg::{Command, DisplayCurrency, Opts, TaxComponents};
 use citizen::Citizen;
-use util::{ApproxRatio, BigUR};
+use exchange::{convert, exchange_rate, load_exchange_rates, set_exchange_rates, CurrencyCode};
+use loc::{supported_states, Location, LocationRegistry};
+use logger::LogFormat;
+use report::{
+    AnalysisReport, BonusTaxes, BracketHeadroom, CapitalGainsTaxes, CareerTaxes, CheapestLocation,
+    Household, Offer, PartialYear, Paychecks, Projection, TreatyCredit,
+};
+use util::{
+    cast_ratio, currency_to_bigur, round_up_to, split_into_periods, ApproxPercent, ApproxRatio,
+    BigUR, Money, UR64,
+};
+
+/// The exit status when the equivalent income is above `--fail-if-above`. Errors exit with 1.
+const EXIT_ABOVE_LIMIT: i32 = 2;
+
+const QUARTERS: u32 = 4;
+
+const DEFAULT_HOURS_PER_WEEK: u32 = 40;
+const DEFAULT_WEEKS_PER_YEAR: u32 = 52;
+
+/// Suggested incomes are rounded up to the next multiple of this, in the display currency.
+const SUGGESTION_STEP: u32 = 1_000;
+
+/// The income of working `annual_hours` at an hourly `rate`.
+fn annualize_hourly_rate(rate: Money, annual_hours: u32) -> Money {
+    rate * BigUR::from_integer(BigUint::from(annual_hours))
+}
+
+/// What `amount` earned `years` from now is worth today, with prices rising by `rate` a year.
+fn deflate(amount: BigUR, rate: UR64, years: usize) -> BigUR {
+    let growth: BigUR = cast_ratio(UR64::one() + rate);
+    amount / num::pow(growth, years)
+}
+
+fn to_amount(value: &Currency, name: &str) -> Money {
+    Money(currency_to_bigur(value).unwrap_or_else(|e| panic!("Unexpected {}: {}", name, e)))
+}
+
+/// The symbol that `value` was entered with, if any. The `currency` crate keeps it private, but it
+/// prints it.
+fn symbol_of(value: &Currency) -> Option<char> {
+    value
+        .to_string()
+        .chars()
+        .find(|c| !c.is_ascii_digit() && !matches!(c, '-' | '.' | ','))
+}
+
+/// Incomes this many times above where the top band starts are likely typos.
+const TOP_BAND_OUTLIER_FACTOR: u32 = 10;
+
+/// Where the top band starts at `loc`, when `income` is far enough above it to be a likely typo.
+fn top_band_far_below(loc: &Location, income: &BigUR, status: MaritalStatus) -> Option<BigUR> {
+    let top = loc
+        .tax_system()?
+        .brackets(status)
+        .and_then(|brackets| brackets.top_separator())?
+        .clone();
+    (*income > top.clone() * BigUint::from(TOP_BAND_OUTLIER_FACTOR)).then_some(top)
+}
+
+fn warn_if_far_above_top_band(loc: &Location, income: &BigUR, status: MaritalStatus) {
+    if let Some(top) = top_band_far_below(loc, income, status) {
+        log::warn!(
+            "The income of {} is over {} times the top band at {} in {}, so double check it.",
+            ApproxRatio(income.clone()),
+            TOP_BAND_OUTLIER_FACTOR,
+            ApproxRatio(top),
+            loc
+        );
+    }
+}
+
+/// Parsing the options logs, so the logger has to be set up first. The `--log-format` is picked out
+/// of the arguments by hand for it, and anything unparseable is left for the real parse to report.
+fn log_format_arg() -> LogFormat {
+    let args: Vec<_> = std::env::args().collect();
+    let value = args.iter().enumerate().find_map(|(idx, arg)| {
+        if arg == "--log-format" {
+            args.get(idx + 1).map(String::as_str)
+        } else {
+            arg.strip_prefix("--log-format=")
+        }
+    });
+    value
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+fn run_command(command: Command) {
+    match command {
+        Command::Check => {
+            let problems = loc::check_built_in_tax_systems();
+            if problems.is_empty() {
+                println!("All built-in tax data checks out.");
+                return;
+            }
+            for problem in problems.iter() {
+                println!("{}", problem);
+            }
+            eprintln!(
+                "Found {} problems in the built-in tax data.",
+                problems.len()
+            );
+            std::process::exit(1);
+        }
+        Command::Batch { input_csv } => match batch::analyze_csv(&input_csv) {
+            Ok(output) => print!("{}", output),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        Command::Decode { scenario } => {
+            analyze(scenario::decode(&scenario).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }));
+        }
+    }
+}
 
 fn main() {
-    logger::setup().expect("the logger to intialize properly.");
+    let log_format = log_format_arg();
+    logger::setup(log_format).expect("the logger to intialize properly.");
+
+    // The analysis takes positional arguments, so commands are only recognized on their own.
+    if let Ok(command) = Command::from_iter_safe(std::env::args()) {
+        run_command(command);
+        return;
+    }
 
     let opts = Opts::from_args();
+    debug_assert_eq!(
+        opts.log_format, log_format,
+        "the log format picked out by hand to match the parsed one."
+    );
+    if opts.encode {
+        println!(
+            "{}",
+            scenario::encode(&Opts {
+                encode: false,
+                ..opts
+            })
+        );
+        return;
+    }
+    analyze(opts);
+}
+
+fn analyze(opts: Opts) {
     log::info!("Attempting to process arguments: {:?}", opts);
-    let (sign, income) = opts.income.value().clone().to_bytes_le();
-    if sign == currency_num::bigint::Sign::Minus {
-        panic!("Unexpected negative income. Terminating.");
-    }
-    let (sign, monthly_expenses) = opts.monthly_expenses.value().clone().to_bytes_le();
-    if sign == currency_num::bigint::Sign::Minus {
-        panic!("Unexpected negative expenses. Terminating.");
-    }
-    let income = BigUint::from_bytes_le(income.as_slice());
-    let monthly_expenses = BigUint::from_bytes_le(monthly_expenses.as_slice());
-
-    let citizen = Citizen {
-        income: BigUR::from_integer(income) / BigUint::from(100u8),
-        monthly_expenses: BigUR::from_integer(monthly_expenses) / BigUint::from(100u8),
-        status: opts.status,
-        home: opts.source,
+    let mut rates = opts
+        .exchange_rate_file
+        .as_ref()
+        .map(|path| {
+            load_exchange_rates(path).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            })
+        })
+        .unwrap_or_default();
+    if let Some(((from, to), rate)) = opts.exchange_rate {
+        // Otherwise the opposite pair from the file would be used for one of the directions.
+        rates.remove(&(to, from));
+        rates.insert((from, to), rate);
+    }
+    set_exchange_rates(rates);
+
+    let mut registry = LocationRegistry::default();
+    for (loc, path) in opts.registered_cities.iter() {
+        let system = TaxSystem::from_file(path, opts.dependents).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        registry.insert(loc.country, loc.state, loc.city.as_str(), system);
+    }
+    loc::set_location_registry(registry);
+
+    let mut income = to_amount(&opts.income, "income");
+    let hourly_rate = opts
+        .hourly_rate
+        .as_ref()
+        .map(|rate| to_amount(rate, "hourly rate"));
+    // The income is positional, so it can't be left out when the hourly rate is given instead.
+    if hourly_rate.is_some() && income != Money::zero() {
+        eprintln!("--hourly-rate replaces the income, so pass 0 for it.");
+        std::process::exit(1);
+    }
+    let annual_hours = opts.hours_per_year.or_else(|| {
+        hourly_rate.is_some().then(|| {
+            opts.hours_per_week.unwrap_or(DEFAULT_HOURS_PER_WEEK)
+                * opts.weeks_per_year.unwrap_or(DEFAULT_WEEKS_PER_YEAR)
+        })
+    });
+    if let Some(rate) = hourly_rate {
+        let hours = annual_hours.expect("the hours to be known for an hourly rate.");
+        income = annualize_hourly_rate(rate, hours);
+        log::info!("Annual income from the hourly rate is {}.", income);
+    }
+    if let Some(months) = opts.months_worked {
+        income = income * BigUR::new(12u8.into(), months.into());
+        log::info!("Annualized income to {}.", income);
+    }
+    // Bonuses are paid once, so they're left out of the annualizing.
+    let bonus = opts.bonus.as_ref().map(|bonus| to_amount(bonus, "bonus"));
+    if let Some(bonus) = bonus.as_ref() {
+        income += bonus.clone();
+    }
+    let monthly_expenses = to_amount(&opts.monthly_expenses, "expenses");
+    if opts.expenses_percent.is_some() && monthly_expenses != Money::zero() {
+        eprintln!("--expenses-percent replaces the monthly expenses, so pass 0 for them.");
+        std::process::exit(1);
+    }
+    let mut household = None;
+    if let Some(spouse_income) = opts.spouse_income.as_ref() {
+        let spouse_income = to_amount(spouse_income, "spouse income");
+        match opts.status {
+            MaritalStatus::Joint => {
+                household = Some((income.clone(), spouse_income.clone()));
+                income += spouse_income;
+            }
+            // Each spouse files their own return, so only the entered income is taxed.
+            MaritalStatus::Separate => log::info!("Ignoring spouse income when filing separately."),
+            _ => {
+                eprintln!(
+                    "Spouse income only applies to joint or separate filing, not {:?}.",
+                    opts.status
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let employer_match = opts
+        .employer_match
+        .as_ref()
+        .map(|employer_match| to_amount(employer_match, "employer match"));
+    let tax_exempt_income = opts
+        .tax_exempt
+        .as_ref()
+        .map_or_else(Money::zero, |exempt| to_amount(exempt, "tax exempt income"));
+
+    let capital_gains = opts
+        .capital_gains
+        .as_ref()
+        .map_or_else(Money::zero, |gains| to_amount(gains, "capital gains"));
+    let loss_carryforward = opts
+        .loss_carryforward
+        .as_ref()
+        .map_or_else(Money::zero, |losses| to_amount(losses, "loss carryforward"));
+
+    let hsa_contribution = opts
+        .hsa_contribution
+        .as_ref()
+        .map_or_else(Money::zero, |hsa| to_amount(hsa, "HSA contribution"));
+
+    let qbi_income = opts
+        .qbi_income
+        .as_ref()
+        .map_or_else(Money::zero, |qbi| to_amount(qbi, "QBI income"));
+    if qbi_income > income {
+        eprintln!("The QBI income has to be part of the income, so it can't be more than it.");
+        std::process::exit(1);
+    }
+
+    let childcare_expenses = opts
+        .childcare_expenses
+        .as_ref()
+        .map_or_else(Money::zero, |care| to_amount(care, "childcare expenses"));
+    if !childcare_expenses.0.is_zero() && opts.dependents == 0 {
+        log::warn!("Childcare expenses only earn a credit with --dependents in care.");
+    }
+
+    let components = opts.include.unwrap_or_default().union(TaxComponents {
+        fica: opts.fica,
+        amt: opts.amt,
+        salt: opts.salt,
+    });
+    log::info!("Including the income tax along with {:?}.", components);
+    if opts.income_is_taxable {
+        if components.salt {
+            eprintln!("The SALT deduction can't be taken off an income that's already taxable.");
+            std::process::exit(1);
+        }
+        log::info!("Taking the income as already taxable, so no deductions are taken off.");
+    }
+    let (fica, ignore_city_tax) = (components.fica, opts.ignore_city_tax);
+    let standard_deduction = opts.standard_deduction;
+    let social_contribution = opts.social_contribution;
+    let bracket_index = opts.bracket_index;
+    if bracket_index.is_some_and(|factor| factor.is_zero()) {
+        eprintln!("The bracket index has to be above zero.");
+        std::process::exit(1);
+    }
+    let configure = move |mut loc: Location| {
+        if fica {
+            loc = loc.with_payroll_taxes();
+        }
+        if ignore_city_tax {
+            loc = loc.without_city_tax();
+        }
+        if standard_deduction {
+            loc = loc.with_standard_deductions();
+        }
+        if social_contribution {
+            loc = loc.with_social_contribution();
+        }
+        if let Some(factor) = bracket_index {
+            loc = loc.with_bracket_index(factor);
+        }
+        loc
     };
+    let home = configure(opts.source);
+    // Amounts entered with a symbol, like `£50k`, are taken to be in its currency.
+    let symbol = symbol_of(&opts.income)
+        .or(opts.hourly_rate.as_ref().and_then(symbol_of))
+        .or(symbol_of(&opts.monthly_expenses));
+    let currency = match (opts.currency, symbol) {
+        (Some(currency), _) => currency,
+        (None, Some(symbol)) => CurrencyCode::of_symbol(symbol).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }),
+        (None, None) => home.currency(),
+    };
+    let mut citizen = Citizen {
+        tax_exempt_income,
+        monthly_expenses,
+        capital_gains,
+        loss_carryforward,
+        hsa_contribution,
+        hsa_coverage: opts.hsa_coverage,
+        qbi_income,
+        childcare_expenses,
+        care_dependents: opts.dependents,
+        currency,
+        amt: components.amt,
+        salt: components.salt,
+        clamp_expenses: opts.clamp_expenses,
+        expense_shares: opts.expense_shares,
+        adjust_cost_of_living: !opts.no_col_adjust,
+        ..Citizen::new(income, opts.status, home)
+    };
+    if let Some(share) = opts.expenses_percent {
+        citizen = citizen.with_expenses_share_of_net(share);
+        log::info!("Monthly expenses budgeted at {}.", citizen.monthly_expenses);
+    }
     log::debug!("Citizen created: {:?}", citizen);
-    let target = opts.target;
+    let mut target = configure(opts.target);
+    if let Some(state) = opts.work_state {
+        target = target.with_work_state(state);
+    }
+    if let Some(path) = opts.extra_tax_file.as_ref() {
+        let extra = TaxSystem::from_file(path, opts.dependents).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        target = target.with_extra_tax_system(extra);
+    }
+    if let Some(rate) = opts.extra_flat_tax {
+        target = target.with_extra_tax_system(TaxSystem::flat(rate));
+    }
+    let locations = if citizen.home == target {
+        vec![&target]
+    } else {
+        vec![&citizen.home, &target]
+    };
+    for loc in locations {
+        let income = convert(&citizen.income.0, citizen.currency, loc.currency());
+        warn_if_far_above_top_band(loc, &income, citizen.status);
+        // Merging hides which systems lacked the status, so they're checked before the merge.
+        let missing: Vec<_> = loc
+            .labelled_tax_systems()
+            .into_iter()
+            .filter(|(_, system)| system.brackets(citizen.status).is_none())
+            .map(|(label, _)| label)
+            .collect();
+        if !missing.is_empty() {
+            let message = format!(
+                "No brackets for {:?} in {} at {}.",
+                citizen.status,
+                missing.join(", "),
+                loc
+            );
+            if opts.strict_statuses {
+                eprintln!("{}", message);
+                std::process::exit(1);
+            }
+            log::warn!("{} They don't tax the income at all.", message);
+        }
+    }
     let mode = opts.usage;
+    // On the standard error, so it doesn't get in the way of the rendered report.
+    if citizen.home == target && !target.has_extra_taxes() && !opts.summary_only {
+        eprintln!("Note: the home and target locations are the same.");
+    }
+
+    if let Some(path) = opts.diff_tax_file.as_ref() {
+        let other = TaxSystem::from_file(path, opts.dependents).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+        let status = citizen.status;
+        let current = target.tax_system();
+        match (
+            current.as_ref().and_then(|sys| sys.brackets(status)),
+            other.brackets(status),
+        ) {
+            (Some(current), Some(other)) => {
+                let diffs = current.diff(other);
+                if diffs.is_empty() {
+                    println!("No differences for {:?}.", status);
+                }
+                for diff in diffs {
+                    println!("{}", diff);
+                }
+            }
+            _ => println!(
+                "Both schedules need brackets for {:?} to be compared.",
+                status
+            ),
+        }
+        return;
+    }
+
+    if opts.dump_merged {
+        for (label, system) in target.labelled_tax_systems() {
+            println!("{} brackets for {:?}:", label, citizen.status);
+            match system.brackets(citizen.status) {
+                Some(brackets) => print!("{}", brackets),
+                None => println!("No brackets."),
+            }
+        }
+        println!("Merged brackets for {:?}:", citizen.status);
+        match target
+            .tax_system()
+            .as_ref()
+            .and_then(|sys| sys.brackets(citizen.status))
+        {
+            Some(brackets) => print!("{}", brackets),
+            None => println!("No brackets."),
+        }
+        println!();
+    }
+
+    if opts.show_schedule {
+        match target
+            .tax_system()
+            .as_ref()
+            .and_then(|sys| sys.brackets(citizen.status))
+        {
+            Some(brackets) => print!("{}", brackets),
+            None => println!("There are no taxes for {:?} at the target.", citizen.status),
+        }
+        return;
+    }
+
+    if let Some(goal) = opts.disposable_goal.as_ref() {
+        let goal = to_amount(goal, "disposable goal");
+        match citizen.calc_gross_for_disposable_at(&target, &goal.0) {
+            Ok(gross) => println!("Gross income needed: {}", ApproxRatio(gross)),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if !opts.rate_curve.is_empty() {
+        let incomes: Vec<_> = opts
+            .rate_curve
+            .iter()
+            .map(|income| to_amount(income, "curve income").0)
+            .collect();
+        for (income, rate) in citizen.effective_rate_curve_at(&target, &incomes) {
+            println!("{}: {}", ApproxRatio(income), ApproxPercent(rate));
+        }
+        return;
+    }
+
+    if !opts.income_stream.is_empty() {
+        let incomes: Vec<_> = opts
+            .income_stream
+            .iter()
+            .map(|income| {
+                let income = to_amount(income, "streamed income");
+                convert(&income.0, citizen.currency, target.currency())
+            })
+            .collect();
+        let taxes = target.tax_system().map_or_else(
+            || vec![BigUR::zero(); incomes.len()],
+            |sys| sys.calc_taxes_stream(&incomes, citizen.status),
+        );
+        let mut total = BigUR::zero();
+        for (year, taxes) in taxes.into_iter().enumerate() {
+            let taxes = convert(&taxes, target.currency(), citizen.currency);
+            println!("Year {}: {}", year + 1, ApproxRatio(taxes.clone()));
+            total += taxes;
+        }
+        println!("Total: {}", ApproxRatio(total));
+        return;
+    }
+
+    if opts.compare_statuses {
+        let mut taxes: Vec<_> = MaritalStatus::ALL
+            .iter()
+            .map(|status| (*status, citizen.calc_taxes_filing_as(&target, *status)))
+            .collect();
+        taxes.sort_by(|(_, lhs), (_, rhs)| lhs.cmp(rhs));
+        for (rank, (status, taxes)) in taxes.into_iter().enumerate() {
+            println!("{}. {:?}: {}", rank + 1, status, ApproxRatio(taxes));
+        }
+        return;
+    }
 
-    let equivalent_income = citizen.estimate_equivalent_income_at(&target, mode);
+    if opts.all_states {
+        let mut taxes: Vec<_> = supported_states()
+            .into_iter()
+            .map(|state| {
+                let loc = configure(Location::new(state.country(), state, "")).without_city_tax();
+                let taxes = citizen.calc_taxes_at(&loc);
+                (loc, taxes)
+            })
+            .collect();
+        taxes.sort_by(|(_, lhs), (_, rhs)| lhs.cmp(rhs));
+        for (rank, (loc, taxes)) in taxes.into_iter().enumerate() {
+            println!(
+                "{}. {} ({}, {}): {}",
+                rank + 1,
+                loc.state,
+                loc.state_name(),
+                loc.country_name(),
+                ApproxRatio(taxes)
+            );
+        }
+        return;
+    }
+
+    if opts.cost_factor_only {
+        let factor = target
+            .get_living_costs_factor()
+            .and_then(|target| Ok(target / citizen.home.get_living_costs_factor()?))
+            .unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            });
+        println!(
+            "Cost of living factor: {} ({})",
+            ApproxRatio(factor.clone()),
+            factor
+        );
+        return;
+    }
+
+    let estimates = citizen
+        .estimate_equivalent_net_at(&target, mode)
+        .and_then(|net| {
+            let income = citizen.estimate_equivalent_income_at(&target, mode)?;
+            Ok((net, income))
+        });
+    let (target_net, equivalent_income) = estimates.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
     log::info!("Equivalent income deduced to be: {}.", equivalent_income);
 
-    // TODO allow for other symbols.
-    let currency_symbol = '$';
-    println!(
-        "Total earned   : {}{}",
-        currency_symbol,
-        ApproxRatio(citizen.income.clone())
-    );
-    println!(
-        "Taxes at home  : {}{}",
-        currency_symbol,
-        ApproxRatio(citizen.calc_taxes())
-    );
-    println!(
-        "Taxes at target: {}{}",
-        currency_symbol,
-        ApproxRatio(citizen.calc_taxes_at(&target))
-    );
+    let display_currency = match opts.display_currency {
+        DisplayCurrency::Home => citizen.currency,
+        DisplayCurrency::Target => target.currency(),
+    };
+    let display_rate = exchange_rate(citizen.currency, display_currency);
+    let display = |amount: BigUR| amount * display_rate.clone();
+
+    let treaty_credit = if !opts.treaty_credit {
+        None
+    } else if citizen.home.country == target.country {
+        log::warn!("Home and target share a country, so there's no treaty credit to apply.");
+        None
+    } else {
+        Some(TreatyCredit {
+            credit: display(citizen.calc_treaty_credit(&target)),
+            total_taxes: display(citizen.calc_taxes_with_treaty_credit(&target)),
+        })
+    };
 
-    println!(
-        r#"Estimated equivalent income at new location:
-    raw output: {}
-    total: {sym}{}"#,
-        equivalent_income,
-        ApproxRatio(equivalent_income.clone()),
-        sym = currency_symbol,
+    let split_year_taxes = match opts.split_year {
+        Some(_) if citizen.home.country != target.country => {
+            log::warn!("Split-year taxes are only supported within a country, so they're skipped.");
+            None
+        }
+        Some(home_share) => Some(display(citizen.calc_split_year_taxes(&target, home_share))),
+        None => None,
+    };
+
+    let cheapest_location = if opts.candidates.is_empty() {
+        None
+    } else {
+        let candidates: Vec<_> = opts.candidates.into_iter().map(configure).collect();
+        let cheapest = citizen.cheapest_tax_location(&candidates);
+        Some(CheapestLocation {
+            location: cheapest.to_string(),
+            taxes: display(citizen.calc_taxes_at(cheapest)),
+        })
+    };
+
+    let target_net = display(target_net);
+    let after_tax_hourly = annual_hours
+        .filter(|hours| *hours > 0)
+        .map(|hours| target_net.clone() / BigUR::from_integer(BigUint::from(hours)));
+    let paychecks = split_into_periods(
+        &target_net,
+        opts.pay_periods,
+        display_currency.minor_units(),
     );
+
+    let target_taxes = display(citizen.calc_taxes_at(&target));
+    let quarterly_taxes = if opts.quarterly {
+        let payments = split_into_periods(&target_taxes, QUARTERS, display_currency.minor_units());
+        Some(Paychecks {
+            count: QUARTERS,
+            regular: payments[0].clone(),
+            last: payments[payments.len() - 1].clone(),
+        })
+    } else {
+        None
+    };
+
+    let projection = match (opts.project_to, opts.inflation_rate) {
+        (Some(year), Some(rate)) => {
+            let years = year - chrono::Local::now().year();
+            if years < 0 {
+                eprintln!("Can't project to {}, which is in the past.", year);
+                std::process::exit(1);
+            }
+            Some(Projection {
+                year,
+                real_income: deflate(display(equivalent_income.clone()), rate, years as usize),
+            })
+        }
+        _ => None,
+    };
+
+    let has_carryforward = opts.loss_carryforward.is_some();
+    let report = AnalysisReport {
+        home: citizen.home.display_name(),
+        target: target.display_name(),
+        currency_symbol: display_currency.symbol(),
+        rounding: opts.rounding,
+        number_style: opts.number_style,
+        precision_exact: opts.precision_exact,
+        inexact_figures: RefCell::new(vec![]),
+        income: display(citizen.total_income()),
+        household: household.map(|(own, spouse)| Household {
+            own_income: display(own.0),
+            spouse_income: display(spouse.0),
+        }),
+        total_compensation: employer_match
+            .map(|employer_match| display(citizen.total_income() + employer_match.0)),
+        home_taxes: display(citizen.calc_taxes()),
+        target_taxes,
+        treaty_credit,
+        capital_gains_taxes: (opts.capital_gains.is_some() || has_carryforward).then(|| {
+            CapitalGainsTaxes {
+                home_taxes: display(citizen.calc_capital_gains_taxes_at(&citizen.home)),
+                target_taxes: display(citizen.calc_capital_gains_taxes_at(&target)),
+                carried_forward: has_carryforward
+                    .then(|| display(citizen.remaining_loss_carryforward())),
+            }
+        }),
+        bonus: bonus.map(|bonus| BonusTaxes {
+            withheld: target
+                .supplemental_withholding_rate()
+                .map(|rate| display(bonus.0.clone() * cast_ratio::<u64, BigUint>(rate))),
+            owed: display(citizen.calc_taxes_on_top_at(&target, &bonus.0)),
+        }),
+        bracket_headroom: opts.bracket_headroom.then(|| BracketHeadroom {
+            to_next_band: citizen.bracket_headroom_at(&target).map(display),
+        }),
+        next_dollar_kept: opts
+            .next_dollar
+            .then(|| cast_ratio(citizen.next_unit_kept_share_at(&target))),
+        partial_year: opts.months_worked.map(|months| {
+            let share = BigUR::new(months.into(), 12u8.into());
+            PartialYear {
+                months,
+                home_taxes: display(citizen.calc_taxes()) * share.clone(),
+                target_taxes: display(citizen.calc_taxes_at(&target)) * share,
+            }
+        }),
+        split_year_taxes,
+        career_taxes: match (opts.career_years, opts.income_growth) {
+            (Some(years), Some(growth)) => Some(CareerTaxes {
+                years,
+                home_taxes: display(citizen.calc_career_taxes_at(&citizen.home, growth, years)),
+                target_taxes: display(citizen.calc_career_taxes_at(&target, growth, years)),
+            }),
+            _ => None,
+        },
+        cheapest_location,
+        projection,
+        offer: opts.offer.as_ref().map(|offer| {
+            let income = to_amount(offer, "offer").0;
+            let offered = citizen
+                .clone()
+                .with_income(income.clone() / display_rate.clone());
+            Offer {
+                take_home: display(offered.calc_net_at(&target)),
+                income,
+            }
+        }),
+        suggested_income: round_up_to(
+            &display(equivalent_income.clone()),
+            &Money::from_dollars_cents(SUGGESTION_STEP.into(), 0).0,
+        ),
+        equivalent_income: display(equivalent_income),
+        after_tax_hourly,
+        paychecks: Paychecks {
+            count: opts.pay_periods,
+            regular: paychecks[0].clone(),
+            last: paychecks[paychecks.len() - 1].clone(),
+        },
+        quarterly_taxes,
+    };
+    let rendered = if opts.summary_only {
+        report.render_summary()
+    } else {
+        report.render(opts.format)
+    };
+    let inexact = report.inexact_figures.take();
+    if !inexact.is_empty() {
+        eprintln!("Some figures aren't exact to the cent:");
+        for figure in inexact {
+            eprintln!("    {}", figure);
+        }
+        std::process::exit(1);
+    }
+    print!("{}", rendered);
+
+    if let Some(limit) = opts.fail_if_above.as_ref() {
+        if report.equivalent_income > to_amount(limit, "limit").0 {
+            std::process::exit(EXIT_ABOVE_LIMIT);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fifty_an_hour_for_forty_hours_over_fifty_weeks_is_a_hundred_thousand() {
+        let income = annualize_hourly_rate(Money::from_dollars_cents(50, 0), 40 * 50);
+        assert_eq!(income, Money::from_dollars_cents(100_000, 0));
+    }
+
+    #[test]
+    fn incomes_far_above_the_top_band_are_flagged() {
+        let austin = Location::parse_with_delimiter("USA///TX///Austin", "///").unwrap();
+        let flagged = |income: u32| {
+            top_band_far_below(
+                &austin,
+                &BigUR::from_integer(income.into()),
+                MaritalStatus::Single,
+            )
+        };
+        assert_eq!(
+            flagged(10_000_000),
+            Some(BigUR::from_integer(518_400u32.into()))
+        );
+        assert_eq!(flagged(600_000), None);
+    }
+
+    #[test]
+    fn ten_years_at_two_percent_deflates_by_the_compounded_rate() {
+        let income = BigUR::from_integer(100_000u32.into());
+        let real = deflate(income.clone(), UR64::new(2, 100), 10);
+        assert_eq!(
+            real.clone(),
+            income.clone() * num::pow(BigUR::new(50u8.into(), 51u8.into()), 10)
+        );
+        assert_eq!(ApproxRatio(real).to_string(), "82034.82 (approx)");
+        assert_eq!(deflate(income.clone(), UR64::new(2, 100), 0), income);
+    }
 }
@@ -0,0 +1,737 @@
+use num::traits::Zero;
+use serde::{Deserialize, Serialize};
+
+use std::cell::RefCell;
+
+use crate::util::{
+    with_percent_sign, BigUR, ExactDecimal, NumberStyle, RoundedApproxRatio, RoundingMode,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum ReportFormat {
+    #[default]
+    Text,
+    Markdown,
+    /// Home and target side by side.
+    Columns,
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "markdown" => Ok(ReportFormat::Markdown),
+            "columns" => Ok(ReportFormat::Columns),
+            _ => Err(format!("Failed to understand report format {:?}.", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReportFormat::Text => write!(f, "text"),
+            ReportFormat::Markdown => write!(f, "markdown"),
+            ReportFormat::Columns => write!(f, "columns"),
+        }
+    }
+}
+
+/// Both earners when a spouse's income is combined under joint filing. The expenses are shared, so
+/// the analysis is of the household.
+#[derive(Debug, Clone)]
+pub struct Household {
+    pub own_income: BigUR,
+    pub spouse_income: BigUR,
+}
+
+#[derive(Debug, Clone)]
+pub struct TreatyCredit {
+    pub credit: BigUR,
+    pub total_taxes: BigUR,
+}
+
+/// The taxes prorated to the months actually worked.
+#[derive(Debug, Clone)]
+pub struct PartialYear {
+    pub months: u32,
+    pub home_taxes: BigUR,
+    pub target_taxes: BigUR,
+}
+
+/// The taxes summed over several years of a growing income.
+#[derive(Debug, Clone)]
+pub struct CareerTaxes {
+    pub years: u32,
+    pub home_taxes: BigUR,
+    pub target_taxes: BigUR,
+}
+
+/// An actual offer at the target.
+#[derive(Debug, Clone)]
+pub struct Offer {
+    pub income: BigUR,
+    /// What's left of the offer at the target after taxes.
+    pub take_home: BigUR,
+}
+
+/// The equivalent income as of a future year, deflated to today's money.
+#[derive(Debug, Clone)]
+pub struct Projection {
+    pub year: i32,
+    pub real_income: BigUR,
+}
+
+#[derive(Debug, Clone)]
+pub struct CapitalGainsTaxes {
+    pub home_taxes: BigUR,
+    pub target_taxes: BigUR,
+    /// The losses left over for the next year, when any were carried forward.
+    pub carried_forward: Option<BigUR>,
+}
+
+/// What a bonus at the target is withheld at compared to what it adds to the taxes.
+#[derive(Debug, Clone)]
+pub struct BonusTaxes {
+    /// At the country's flat supplemental rate, if it has one.
+    pub withheld: Option<BigUR>,
+    /// At the marginal rates of every tax, on top of the rest of the income.
+    pub owed: BigUR,
+}
+
+/// The income that fits in the current band at the target before the next one starts.
+#[derive(Debug, Clone)]
+pub struct BracketHeadroom {
+    /// `None` in the top band.
+    pub to_next_band: Option<BigUR>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheapestLocation {
+    pub location: String,
+    pub taxes: BigUR,
+}
+
+#[derive(Debug, Clone)]
+pub struct Paychecks {
+    pub count: u32,
+    pub regular: BigUR,
+    pub last: BigUR,
+}
+
+/// Everything printed for an analysis, with all amounts in the display currency.
+#[derive(Debug, Clone)]
+pub struct AnalysisReport {
+    pub home: String,
+    pub target: String,
+    pub currency_symbol: char,
+    pub rounding: RoundingMode,
+    pub number_style: NumberStyle,
+    /// Whether figures are only displayed when they're exact to the cent, instead of rounded.
+    pub precision_exact: bool,
+    /// Every figure that wasn't exact while rendering with `precision_exact`.
+    pub inexact_figures: RefCell<Vec<String>>,
+    pub income: BigUR,
+    pub household: Option<Household>,
+    /// The income along with compensation that isn't taxed now, like an employer's match.
+    pub total_compensation: Option<BigUR>,
+    pub home_taxes: BigUR,
+    pub target_taxes: BigUR,
+    pub treaty_credit: Option<TreatyCredit>,
+    pub capital_gains_taxes: Option<CapitalGainsTaxes>,
+    pub bonus: Option<BonusTaxes>,
+    pub bracket_headroom: Option<BracketHeadroom>,
+    /// The share of the next unit earned at the target that's kept after taxes.
+    pub next_dollar_kept: Option<BigUR>,
+    pub partial_year: Option<PartialYear>,
+    /// The taxes when moving partway through the year.
+    pub split_year_taxes: Option<BigUR>,
+    pub career_taxes: Option<CareerTaxes>,
+    pub cheapest_location: Option<CheapestLocation>,
+    pub equivalent_income: BigUR,
+    pub projection: Option<Projection>,
+    /// An actual offer at the target, to compare against the equivalent income.
+    pub offer: Option<Offer>,
+    /// The equivalent income rounded up to a figure that offers are usually stated in.
+    pub suggested_income: BigUR,
+    /// The take-home at the target for each hour worked.
+    pub after_tax_hourly: Option<BigUR>,
+    pub paychecks: Paychecks,
+    /// The taxes at the target split into estimated payments.
+    pub quarterly_taxes: Option<Paychecks>,
+}
+
+impl AnalysisReport {
+    /// Rounded by the rounding mode, unless `precision_exact` is on, in which case a figure that
+    /// isn't exact is noted in `inexact_figures` and shown as `?`.
+    fn decimal(&self, value: &BigUR) -> String {
+        if !self.precision_exact {
+            return RoundedApproxRatio(value.clone(), self.rounding, self.number_style).to_string();
+        }
+        match ExactDecimal::new(value.clone(), self.number_style) {
+            Ok(exact) => exact.to_string(),
+            Err(e) => {
+                self.inexact_figures.borrow_mut().push(e);
+                "?".to_owned()
+            }
+        }
+    }
+
+    fn money(&self, amount: &BigUR) -> String {
+        format!("{}{}", self.currency_symbol, self.decimal(amount))
+    }
+
+    fn household_summary(&self, household: &Household) -> String {
+        format!(
+            "{} + {}, filing jointly",
+            self.money(&household.own_income),
+            self.money(&household.spouse_income)
+        )
+    }
+
+    fn paychecks_summary(&self, paychecks: &Paychecks) -> String {
+        format!(
+            "{} x {}, with the last being {}",
+            paychecks.count,
+            self.money(&paychecks.regular),
+            self.money(&paychecks.last),
+        )
+    }
+
+    /// Going from the home taxes to the target taxes, so a saving is negative.
+    fn tax_change_summary(&self) -> String {
+        match self.target_taxes.cmp(&self.home_taxes) {
+            std::cmp::Ordering::Less => format!(
+                "-{} (saving)",
+                self.money(&(self.home_taxes.clone() - self.target_taxes.clone()))
+            ),
+            std::cmp::Ordering::Greater => format!(
+                "+{} (increase)",
+                self.money(&(self.target_taxes.clone() - self.home_taxes.clone()))
+            ),
+            std::cmp::Ordering::Equal => format!("{} (no change)", self.money(&BigUR::zero())),
+        }
+    }
+
+    /// The share of the income going to `taxes`.
+    fn effective_rate(&self, taxes: &BigUR) -> BigUR {
+        if self.income.is_zero() {
+            BigUR::zero()
+        } else {
+            taxes.clone() / self.income.clone()
+        }
+    }
+
+    fn percent(&self, rate: &BigUR) -> String {
+        let hundred = BigUR::from_integer(100u8.into());
+        with_percent_sign(&self.decimal(&(rate.clone() * hundred)))
+    }
+
+    /// Both effective rates, how far the target's is from the home's, and their average.
+    fn effective_rates_summary(&self) -> String {
+        let home = self.effective_rate(&self.home_taxes);
+        let target = self.effective_rate(&self.target_taxes);
+        let change = if target >= home {
+            format!("+{}", self.percent(&(target.clone() - home.clone())))
+        } else {
+            format!("-{}", self.percent(&(home.clone() - target.clone())))
+        };
+        let blended = (home.clone() + target.clone()) / BigUR::from_integer(2u8.into());
+        format!(
+            "{} at home, {} at target ({}), {} blended",
+            self.percent(&home),
+            self.percent(&target),
+            change,
+            self.percent(&blended)
+        )
+    }
+
+    /// How much the equivalent income is over the income, as a share of it, so a cheaper target
+    /// needs a negative raise.
+    fn raise_summary(&self) -> String {
+        if self.income.is_zero() {
+            return "unknown without an income".to_owned();
+        }
+        if self.equivalent_income >= self.income {
+            let raise =
+                (self.equivalent_income.clone() - self.income.clone()) / self.income.clone();
+            format!("+{} to break even", self.percent(&raise))
+        } else {
+            let cut = (self.income.clone() - self.equivalent_income.clone()) / self.income.clone();
+            format!(
+                "-{} to break even (the target is cheaper)",
+                self.percent(&cut)
+            )
+        }
+    }
+
+    fn partial_year_summary(&self, partial: &PartialYear) -> String {
+        format!(
+            "{} at home, {} at target over {} months",
+            self.money(&partial.home_taxes),
+            self.money(&partial.target_taxes),
+            partial.months
+        )
+    }
+
+    fn bonus_summary(&self, bonus: &BonusTaxes) -> String {
+        let withheld = match bonus.withheld.as_ref() {
+            Some(withheld) => format!("{} withheld", self.money(withheld)),
+            None => "no flat withholding".to_owned(),
+        };
+        format!("{}, {} owed at target", withheld, self.money(&bonus.owed))
+    }
+
+    fn bracket_headroom_summary(&self, headroom: &BracketHeadroom) -> String {
+        match headroom.to_next_band.as_ref() {
+            Some(room) => format!("{} before the next band", self.money(room)),
+            None => "none, since it's the top band".to_owned(),
+        }
+    }
+
+    fn next_dollar_summary(&self, kept: &BigUR) -> String {
+        format!(
+            "keep {} of it at target ({})",
+            self.money(kept),
+            self.percent(kept)
+        )
+    }
+
+    fn capital_gains_summary(&self, gains: &CapitalGainsTaxes) -> String {
+        let summary = format!(
+            "{} at home, {} at target",
+            self.money(&gains.home_taxes),
+            self.money(&gains.target_taxes)
+        );
+        match gains.carried_forward.as_ref() {
+            Some(losses) => format!("{}, with {} of losses left", summary, self.money(losses)),
+            None => summary,
+        }
+    }
+
+    /// Going from the equivalent income to the offer, so a shortfall is negative.
+    fn offer_summary(&self, offer: &Offer) -> String {
+        let take_home = &offer.take_home;
+        let offer = &offer.income;
+        let difference = match offer.cmp(&self.equivalent_income) {
+            std::cmp::Ordering::Greater => format!(
+                "+{} over the equivalent income (a gain)",
+                self.money(&(offer.clone() - self.equivalent_income.clone()))
+            ),
+            std::cmp::Ordering::Less => format!(
+                "-{} under the equivalent income (a loss)",
+                self.money(&(self.equivalent_income.clone() - offer.clone()))
+            ),
+            std::cmp::Ordering::Equal => "the same as the equivalent income".to_owned(),
+        };
+        format!(
+            "{}, {}, taking home {}",
+            self.money(offer),
+            difference,
+            self.money(take_home)
+        )
+    }
+
+    fn projection_summary(&self, projection: &Projection) -> String {
+        format!(
+            "{} (if earned in {})",
+            self.money(&projection.real_income),
+            projection.year
+        )
+    }
+
+    fn career_summary(&self, career: &CareerTaxes) -> String {
+        format!(
+            "{} at home, {} at target over {} years",
+            self.money(&career.home_taxes),
+            self.money(&career.target_taxes),
+            career.years
+        )
+    }
+
+    fn cheapest_summary(&self, cheapest: &CheapestLocation) -> String {
+        format!("{} ({})", cheapest.location, self.money(&cheapest.taxes))
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Text => self.render_text(),
+            ReportFormat::Markdown => self.render_markdown(),
+            ReportFormat::Columns => self.render_columns(),
+        }
+    }
+
+    /// Just the equivalent income, formatted and raw, on a single line for scripts.
+    pub fn render_summary(&self) -> String {
+        format!(
+            "{} ({})\n",
+            self.money(&self.equivalent_income),
+            self.equivalent_income
+        )
+    }
+
+    fn render_text(&self) -> String {
+        let mut lines = vec![
+            format!("Moving from    : {}", self.home),
+            format!("Moving to      : {}", self.target),
+            format!("Total earned   : {}", self.money(&self.income)),
+        ];
+        if let Some(household) = self.household.as_ref() {
+            lines.push(format!(
+                "Household      : {}",
+                self.household_summary(household)
+            ));
+        }
+        if let Some(total) = self.total_compensation.as_ref() {
+            lines.push(format!("Total comp     : {}", self.money(total)));
+        }
+        lines.extend(vec![
+            format!("Taxes at home  : {}", self.money(&self.home_taxes)),
+            format!("Taxes at target: {}", self.money(&self.target_taxes)),
+            format!("Tax change     : {}", self.tax_change_summary()),
+            format!("Effective rates: {}", self.effective_rates_summary()),
+        ]);
+        if let Some(treaty) = self.treaty_credit.as_ref() {
+            lines.push(format!("Treaty credit  : {}", self.money(&treaty.credit)));
+            lines.push(format!(
+                "Taxes on both  : {}",
+                self.money(&treaty.total_taxes)
+            ));
+        }
+        if let Some(bonus) = self.bonus.as_ref() {
+            lines.push(format!("Bonus taxes    : {}", self.bonus_summary(bonus)));
+        }
+        if let Some(headroom) = self.bracket_headroom.as_ref() {
+            lines.push(format!(
+                "Bracket room   : {}",
+                self.bracket_headroom_summary(headroom)
+            ));
+        }
+        if let Some(kept) = self.next_dollar_kept.as_ref() {
+            lines.push(format!(
+                "Next dollar    : {}",
+                self.next_dollar_summary(kept)
+            ));
+        }
+        if let Some(gains) = self.capital_gains_taxes.as_ref() {
+            lines.push(format!(
+                "Gains taxes    : {}",
+                self.capital_gains_summary(gains)
+            ));
+        }
+        if let Some(partial) = self.partial_year.as_ref() {
+            lines.push(format!(
+                "Partial year   : {}",
+                self.partial_year_summary(partial)
+            ));
+        }
+        if let Some(split) = self.split_year_taxes.as_ref() {
+            lines.push(format!("Split-year tax : {}", self.money(split)));
+        }
+        if let Some(career) = self.career_taxes.as_ref() {
+            lines.push(format!("Career taxes   : {}", self.career_summary(career)));
+        }
+        if let Some(cheapest) = self.cheapest_location.as_ref() {
+            lines.push(format!(
+                "Lowest taxes at: {}",
+                self.cheapest_summary(cheapest)
+            ));
+        }
+        lines.push(format!(
+            r#"Estimated equivalent income at new location:
+    raw output: {}
+    total: {}"#,
+            self.equivalent_income,
+            self.money(&self.equivalent_income),
+        ));
+        if let Some(offer) = self.offer.as_ref() {
+            lines.push(format!("Offer          : {}", self.offer_summary(offer)));
+        }
+        if let Some(projection) = self.projection.as_ref() {
+            lines.push(format!(
+                "Worth today    : {}",
+                self.projection_summary(projection)
+            ));
+        }
+        lines.push(format!("Raise needed   : {}", self.raise_summary()));
+        lines.push(format!(
+            "Suggested ask  : {}",
+            self.money(&self.suggested_income)
+        ));
+        if let Some(hourly) = self.after_tax_hourly.as_ref() {
+            lines.push(format!("Net per hour   : {}", self.money(hourly)));
+        }
+        lines.push(format!(
+            "Paychecks      : {}",
+            self.paychecks_summary(&self.paychecks)
+        ));
+        if let Some(quarterly) = self.quarterly_taxes.as_ref() {
+            lines.push(format!(
+                "Quarterly taxes: {}",
+                self.paychecks_summary(quarterly)
+            ));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut rows = vec![
+            ("Moving from", self.home.clone()),
+            ("Moving to", self.target.clone()),
+            ("Total earned", self.money(&self.income)),
+        ];
+        if let Some(household) = self.household.as_ref() {
+            rows.push(("Household", self.household_summary(household)));
+        }
+        if let Some(total) = self.total_compensation.as_ref() {
+            rows.push(("Total comp", self.money(total)));
+        }
+        rows.extend(vec![
+            ("Taxes at home", self.money(&self.home_taxes)),
+            ("Taxes at target", self.money(&self.target_taxes)),
+            ("Tax change", self.tax_change_summary()),
+            ("Effective rates", self.effective_rates_summary()),
+        ]);
+        if let Some(treaty) = self.treaty_credit.as_ref() {
+            rows.push(("Treaty credit", self.money(&treaty.credit)));
+            rows.push(("Taxes on both", self.money(&treaty.total_taxes)));
+        }
+        if let Some(bonus) = self.bonus.as_ref() {
+            rows.push(("Bonus taxes", self.bonus_summary(bonus)));
+        }
+        if let Some(headroom) = self.bracket_headroom.as_ref() {
+            rows.push(("Bracket room", self.bracket_headroom_summary(headroom)));
+        }
+        if let Some(kept) = self.next_dollar_kept.as_ref() {
+            rows.push(("Next dollar", self.next_dollar_summary(kept)));
+        }
+        if let Some(gains) = self.capital_gains_taxes.as_ref() {
+            rows.push(("Gains taxes", self.capital_gains_summary(gains)));
+        }
+        if let Some(partial) = self.partial_year.as_ref() {
+            rows.push(("Partial year", self.partial_year_summary(partial)));
+        }
+        if let Some(split) = self.split_year_taxes.as_ref() {
+            rows.push(("Split-year tax", self.money(split)));
+        }
+        if let Some(career) = self.career_taxes.as_ref() {
+            rows.push(("Career taxes", self.career_summary(career)));
+        }
+        if let Some(cheapest) = self.cheapest_location.as_ref() {
+            rows.push(("Lowest taxes at", self.cheapest_summary(cheapest)));
+        }
+        rows.push(("Equivalent income", self.money(&self.equivalent_income)));
+        rows.push((
+            "Equivalent income (raw)",
+            self.equivalent_income.to_string(),
+        ));
+        if let Some(offer) = self.offer.as_ref() {
+            rows.push(("Offer", self.offer_summary(offer)));
+        }
+        if let Some(projection) = self.projection.as_ref() {
+            rows.push(("Worth today", self.projection_summary(projection)));
+        }
+        rows.push(("Raise needed", self.raise_summary()));
+        rows.push(("Suggested ask", self.money(&self.suggested_income)));
+        if let Some(hourly) = self.after_tax_hourly.as_ref() {
+            rows.push(("Net per hour", self.money(hourly)));
+        }
+        rows.push(("Paychecks", self.paychecks_summary(&self.paychecks)));
+        if let Some(quarterly) = self.quarterly_taxes.as_ref() {
+            rows.push(("Quarterly taxes", self.paychecks_summary(quarterly)));
+        }
+
+        let mut table = String::from("| Field | Value |\n| --- | --- |\n");
+        for (label, value) in rows {
+            table.push_str(&format!("| {} | {} |\n", label, value));
+        }
+        table
+    }
+
+    /// The income, taxes, effective rate and net at home and at the target in aligned columns,
+    /// followed by the equivalent income.
+    fn render_columns(&self) -> String {
+        let net = |taxes: &BigUR| {
+            if *taxes > self.income {
+                BigUR::zero()
+            } else {
+                self.income.clone() - taxes.clone()
+            }
+        };
+        let rows = [
+            ("", self.home.clone(), self.target.clone()),
+            ("Gross", self.money(&self.income), self.money(&self.income)),
+            (
+                "Taxes",
+                self.money(&self.home_taxes),
+                self.money(&self.target_taxes),
+            ),
+            (
+                "Effective rate",
+                self.percent(&self.effective_rate(&self.home_taxes)),
+                self.percent(&self.effective_rate(&self.target_taxes)),
+            ),
+            (
+                "Net",
+                self.money(&net(&self.home_taxes)),
+                self.money(&net(&self.target_taxes)),
+            ),
+        ];
+        let label_width = rows.iter().map(|row| row.0.len()).max().unwrap_or(0);
+        let home_width = rows
+            .iter()
+            .map(|row| row.1.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut lines: Vec<_> = rows
+            .iter()
+            .map(|(label, home, target)| {
+                format!(
+                    "{:<label_width$} | {:<home_width$} | {}",
+                    label,
+                    home,
+                    target,
+                    label_width = label_width,
+                    home_width = home_width,
+                )
+            })
+            .collect();
+        lines.push(format!(
+            "Equivalent income at target: {}",
+            self.money(&self.equivalent_income)
+        ));
+        lines.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dollars(amount: u64) -> BigUR {
+        BigUR::from_integer(amount.into())
+    }
+
+    /// Moving for $100,000 with none of the optional figures.
+    fn sample() -> AnalysisReport {
+        AnalysisReport {
+            home: "Austin, Texas, United States of America".to_owned(),
+            target: "SF, California, United States of America".to_owned(),
+            currency_symbol: '$',
+            rounding: RoundingMode::default(),
+            number_style: NumberStyle::default(),
+            precision_exact: false,
+            inexact_figures: RefCell::new(vec![]),
+            income: dollars(100_000),
+            household: None,
+            total_compensation: None,
+            home_taxes: dollars(18_000),
+            target_taxes: dollars(27_000),
+            treaty_credit: None,
+            capital_gains_taxes: None,
+            bonus: None,
+            bracket_headroom: None,
+            next_dollar_kept: None,
+            partial_year: None,
+            split_year_taxes: None,
+            career_taxes: None,
+            cheapest_location: None,
+            equivalent_income: dollars(113_500),
+            projection: None,
+            offer: None,
+            suggested_income: dollars(114_000),
+            after_tax_hourly: None,
+            paychecks: Paychecks {
+                count: 26,
+                regular: dollars(3_000),
+                last: dollars(3_000),
+            },
+            quarterly_taxes: None,
+        }
+    }
+
+    #[test]
+    fn markdown_is_a_table_of_the_labelled_fields() {
+        let markdown = sample().render(ReportFormat::Markdown);
+        let mut lines = markdown.lines();
+        assert_eq!(lines.next(), Some("| Field | Value |"));
+        assert_eq!(lines.next(), Some("| --- | --- |"));
+        assert!(lines.all(|line| line.starts_with("| ") && line.ends_with(" |")));
+        for label in [
+            "Moving from",
+            "Taxes at home",
+            "Taxes at target",
+            "Equivalent income",
+        ] {
+            assert!(markdown.contains(&format!("| {} | ", label)), "{}", label);
+        }
+        assert!(markdown.contains("| Total earned | $100,000.00"));
+    }
+
+    #[test]
+    fn effective_rate_change_is_the_target_rate_less_the_home_one() {
+        let report = sample();
+        let change =
+            report.effective_rate(&report.target_taxes) - report.effective_rate(&report.home_taxes);
+        assert_eq!(change, BigUR::new(9u8.into(), 100u8.into()));
+        assert_eq!(
+            report.effective_rates_summary(),
+            "18.00% (approx) at home, 27.00% (approx) at target (+9.00% (approx)), \
+             22.50% (approx) blended"
+        );
+        let moving_back = AnalysisReport {
+            home_taxes: dollars(27_000),
+            target_taxes: dollars(18_000),
+            ..sample()
+        };
+        assert!(moving_back
+            .effective_rates_summary()
+            .contains("(-9.00% (approx))"));
+    }
+
+    #[test]
+    fn moving_somewhere_cheaper_needs_a_negative_raise() {
+        assert_eq!(sample().raise_summary(), "+13.50% (approx) to break even");
+        let cheaper = AnalysisReport {
+            equivalent_income: dollars(88_000),
+            ..sample()
+        };
+        assert_eq!(
+            cheaper.raise_summary(),
+            "-12.00% (approx) to break even (the target is cheaper)"
+        );
+    }
+
+    #[test]
+    fn columns_put_the_home_figures_beside_the_target_ones() {
+        let columns = sample().render(ReportFormat::Columns);
+        let rows: Vec<Vec<_>> = columns
+            .lines()
+            .take(5)
+            .map(|line| line.split(" | ").map(str::trim).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    "",
+                    "Austin, Texas, United States of America",
+                    "SF, California, United States of America",
+                ],
+                vec!["Gross", "$100,000.00 (approx)", "$100,000.00 (approx)"],
+                vec!["Taxes", "$18,000.00 (approx)", "$27,000.00 (approx)"],
+                vec!["Effective rate", "18.00% (approx)", "27.00% (approx)"],
+                vec!["Net", "$82,000.00 (approx)", "$73,000.00 (approx)"],
+            ]
+        );
+        // Each column starts at the same place on every line.
+        let starts: Vec<_> = columns
+            .lines()
+            .take(5)
+            .map(|line| line.find(" | "))
+            .collect();
+        assert!(starts.windows(2).all(|pair| pair[0] == pair[1]));
+        assert!(columns.ends_with("Equivalent income at target: $113,500.00 (approx)\n"));
+    }
+}
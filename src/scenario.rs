@@ -0,0 +1,118 @@
+use crate::cfg::Opts;
+
+/// The URL-safe base64 alphabet. Encoded scenarios aren't padded.
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, byte)| {
+            bits | u32::from(*byte) << (16 - 8 * i)
+        });
+        // Every 8 bits take up 6 bits of a character, so n bytes take n + 1 characters.
+        for i in 0..=chunk.len() {
+            encoded.push(ALPHABET[(bits >> (18 - 6 * i)) as usize & 63] as char);
+        }
+    }
+    encoded
+}
+
+fn decode_base64(encoded: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    for chunk in encoded.as_bytes().chunks(4) {
+        if chunk.len() == 1 {
+            return Err("The scenario is cut off.".to_owned());
+        }
+        let mut bits = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let value = ALPHABET
+                .iter()
+                .position(|a| a == c)
+                .ok_or_else(|| format!("The scenario can't contain {:?}.", *c as char))?;
+            bits |= (value as u32) << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            bytes.push((bits >> (16 - 8 * i)) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Encodes the options of a run, as resolved from both the arguments and the environment, as JSON
+/// in base64 so that they can be shared in a single word.
+pub fn encode(opts: &Opts) -> String {
+    let json = serde_json::to_vec(opts).expect("the options to serialize.");
+    encode_base64(&json)
+}
+
+/// Gives back the options that `encode` was given.
+pub fn decode(scenario: &str) -> Result<Opts, String> {
+    let json = decode_base64(scenario.trim())?;
+    serde_json::from_slice(&json).map_err(|e| format!("Could not read the scenario: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_come_back_from_their_base64() {
+        let bytes = b"[\"USA///TX///Austin\"]";
+        // Every length of the last chunk of bytes.
+        for len in bytes.len() - 3..=bytes.len() {
+            let encoded = encode_base64(&bytes[..len]);
+            assert!(
+                encoded.bytes().all(|c| ALPHABET.contains(&c)),
+                "{}",
+                encoded
+            );
+            assert_eq!(decode_base64(&encoded), Ok(bytes[..len].to_vec()));
+        }
+    }
+
+    #[test]
+    fn options_come_back_from_their_encoding() {
+        use structopt::StructOpt;
+
+        let opts = Opts::from_iter(&[
+            "equinc",
+            "USA///TX///Austin",
+            "GBR///ENG///London",
+            "$1,234,567.89",
+            "2k",
+            "joint",
+            "--spouse-income",
+            "£50k",
+            "--exchange-rate",
+            "USD/GBP=0.75",
+            "--include",
+            "fica,amt",
+            "--split-year",
+            "50%",
+            "--expense-share",
+            "housing=40%",
+            "--stream-income",
+            "10k",
+            "--stream-income",
+            "0.07",
+            "--candidate",
+            "USA///CA///SF",
+            "--register-city",
+            "USA///TX///Dallas=dallas.json",
+            "--work-state",
+            "CA",
+            "--format",
+            "markdown",
+        ]);
+        assert_eq!(decode(&encode(&opts)), Ok(opts));
+    }
+
+    #[test]
+    fn scenarios_outside_the_alphabet_are_rejected() {
+        assert_eq!(
+            decode("WyJ+Il0"),
+            Err("The scenario can't contain '+'.".to_owned())
+        );
+        assert_eq!(decode("W"), Err("The scenario is cut off.".to_owned()));
+    }
+}
@@ -1,4 +1,10 @@
-use num::{bigint::BigUint, integer::Integer, rational::Ratio};
+use num::{
+    bigint::BigUint,
+    integer::Integer,
+    rational::Ratio,
+    traits::{CheckedDiv, One, Zero},
+};
+use serde::{Deserialize, Serialize};
 
 pub type UR64 = Ratio<u64>;
 pub type BigUR = Ratio<BigUint>;
@@ -12,14 +18,462 @@ where
     Ratio::new(numer.into(), denom.into())
 }
 
+/// The `currency` crate keeps amounts as a count of cents, in its own version of `num`, so the
+/// count goes through its decimal digits. Amounts can't be negative.
+pub fn currency_to_bigur(value: &currency::Currency) -> Result<BigUR, String> {
+    if value.sign() == currency_num::bigint::Sign::Minus {
+        return Err("Expected an amount that isn't negative.".to_owned());
+    }
+    let cents: BigUint = value
+        .value()
+        .to_str_radix(10)
+        .parse()
+        .map_err(|e| format!("Could not read the cents of {}: {}", value, e))?;
+    Ok(BigUR::new(cents, BigUint::from(100u8)))
+}
+
+/// A monetary amount, so that it isn't mixed up with a rate. Math beyond adding, subtracting and
+/// scaling is done on the inner ratio.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Money(pub BigUR);
+
+impl Money {
+    pub fn zero() -> Self {
+        Self(BigUR::zero())
+    }
+
+    /// An exact amount of whole units and cents, so `cents` has to be below 100.
+    pub fn from_dollars_cents(dollars: u64, cents: u8) -> Self {
+        assert!(cents < 100, "Expected fewer than 100 cents, not {}.", cents);
+        let cents = BigUint::from(dollars) * BigUint::from(100u8) + BigUint::from(cents);
+        Self(BigUR::new(cents, BigUint::from(100u8)))
+    }
+}
+
+impl std::ops::Add for Money {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl std::ops::AddAssign for Money {
+    fn add_assign(&mut self, rhs: Self) {
+        self.0 += rhs.0;
+    }
+}
+
+/// Panics if `rhs` is larger, since amounts can't go negative.
+impl std::ops::Sub for Money {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl std::ops::Mul<BigUR> for Money {
+    type Output = Self;
+    fn mul(self, rhs: BigUR) -> Self {
+        Self(self.0 * rhs)
+    }
+}
+
+/// Truncated to the cent.
+impl std::fmt::Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hundred = BigUint::from(100u8);
+        let cents = (self.0.clone() * BigUR::from_integer(hundred.clone())).to_integer();
+        let (dollars, cents) = cents.div_rem(&hundred);
+        write!(f, "{}.{:02}", dollars, cents)
+    }
+}
+
+/// How the digits past the cents are dropped when displaying a ratio.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum RoundingMode {
+    #[default]
+    Truncate,
+    HalfUp,
+    HalfEven,
+}
+
+impl std::str::FromStr for RoundingMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "truncate" => Ok(RoundingMode::Truncate),
+            "half_up" => Ok(RoundingMode::HalfUp),
+            "half_even" => Ok(RoundingMode::HalfEven),
+            _ => Err(format!("Failed to understand rounding mode {:?}.", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for RoundingMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RoundingMode::Truncate => write!(f, "truncate"),
+            RoundingMode::HalfUp => write!(f, "half_up"),
+            RoundingMode::HalfEven => write!(f, "half_even"),
+        }
+    }
+}
+
+/// The separators used when displaying a number.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum NumberStyle {
+    /// No grouping, with a `.` before the cents, like `518400.00`.
+    Plain,
+    /// Like `518,400.00`.
+    #[default]
+    Us,
+    /// Like `518.400,00`.
+    German,
+}
+
+impl NumberStyle {
+    fn decimal_separator(self) -> char {
+        match self {
+            NumberStyle::Plain | NumberStyle::Us => '.',
+            NumberStyle::German => ',',
+        }
+    }
+
+    fn grouping_separator(self) -> Option<char> {
+        match self {
+            NumberStyle::Plain => None,
+            NumberStyle::Us => Some(','),
+            NumberStyle::German => Some('.'),
+        }
+    }
+
+    fn group(self, digits: String) -> String {
+        let separator = match self.grouping_separator() {
+            Some(separator) => separator,
+            None => return digits,
+        };
+        let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push(separator);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+}
+
+impl std::str::FromStr for NumberStyle {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(NumberStyle::Plain),
+            "us" => Ok(NumberStyle::Us),
+            "german" => Ok(NumberStyle::German),
+            _ => Err(format!("Failed to understand number style {:?}.", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for NumberStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NumberStyle::Plain => write!(f, "plain"),
+            NumberStyle::Us => write!(f, "us"),
+            NumberStyle::German => write!(f, "german"),
+        }
+    }
+}
+
+/// Always truncates, without grouping. Use `RoundedApproxRatio` to pick how to round and group.
 pub struct ApproxRatio<T>(pub Ratio<T>);
 
 impl<T: Clone + std::fmt::Display + Integer + From<u8>> std::fmt::Display for ApproxRatio<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let r = self.0.clone();
-        let ratio_100: Ratio<T> = Ratio::from_integer(100u8.into());
-        let trunc = r.to_integer();
-        let fract = (r.fract() * ratio_100.clone() % ratio_100).to_integer();
-        write!(f, "{}.{:02} (approx)", trunc, fract)
+        RoundedApproxRatio(self.0.clone(), RoundingMode::Truncate, NumberStyle::Plain).fmt(f)
+    }
+}
+
+pub struct RoundedApproxRatio<T>(pub Ratio<T>, pub RoundingMode, pub NumberStyle);
+
+impl<T: Clone + std::fmt::Display + Integer + From<u8>> std::fmt::Display
+    for RoundedApproxRatio<T>
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hundred: T = 100u8.into();
+        let scaled = self.0.clone() * Ratio::from_integer(hundred.clone());
+        let mut cents = scaled.to_integer();
+        let rest = scaled.fract();
+        let half = Ratio::new(T::one(), 2u8.into());
+        let round_up = match self.1 {
+            RoundingMode::Truncate => false,
+            RoundingMode::HalfUp => rest >= half,
+            RoundingMode::HalfEven => rest > half || (rest == half && cents.is_odd()),
+        };
+        if round_up {
+            cents = cents + T::one();
+        }
+        let (whole, fract) = cents.div_rem(&hundred);
+        write!(
+            f,
+            "{}{}{:02}{}",
+            self.2.group(whole.to_string()),
+            self.2.decimal_separator(),
+            fract,
+            APPROX_MARKER
+        )
+    }
+}
+
+/// A rate shown as a truncated percentage, like `ApproxRatio` shows other figures.
+pub struct ApproxPercent<T>(pub Ratio<T>);
+
+impl<T: Clone + std::fmt::Display + Integer + From<u8>> std::fmt::Display for ApproxPercent<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hundred = Ratio::from_integer(100u8.into());
+        let figure = ApproxRatio(self.0.clone() * hundred).to_string();
+        write!(f, "{}", with_percent_sign(&figure))
+    }
+}
+
+/// Follows every figure that `RoundedApproxRatio` displays.
+pub const APPROX_MARKER: &str = " (approx)";
+
+/// Marks a displayed figure as a percentage, keeping any `(approx)` after the sign.
+pub fn with_percent_sign(figure: &str) -> String {
+    match figure.strip_suffix(APPROX_MARKER) {
+        Some(figure) => format!("{}%{}", figure, APPROX_MARKER),
+        None => format!("{}%", figure),
+    }
+}
+
+/// A ratio that's a whole number of cents, so it displays without rounding or `(approx)`.
+pub struct ExactDecimal(BigUR, NumberStyle);
+
+impl ExactDecimal {
+    pub fn new(value: BigUR, style: NumberStyle) -> Result<Self, String> {
+        let cents = value.clone() * BigUR::from_integer(BigUint::from(100u8));
+        if cents.is_integer() {
+            Ok(Self(value, style))
+        } else {
+            Err(format!(
+                "{} ({}) doesn't end at the cent",
+                value,
+                ApproxRatio(value.clone())
+            ))
+        }
+    }
+}
+
+impl std::fmt::Display for ExactDecimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hundred = BigUint::from(100u8);
+        let cents = (self.0.clone() * BigUR::from_integer(hundred.clone())).to_integer();
+        let (whole, fract) = cents.div_rem(&hundred);
+        write!(
+            f,
+            "{}{}{:02}",
+            self.1.group(whole.to_string()),
+            self.1.decimal_separator(),
+            fract
+        )
+    }
+}
+
+/// Splits `total` into `periods` payments rounded down to the smallest unit of a currency with
+/// `minor_units` decimals, with the last payment absorbing the rounding so that they sum back to
+/// exactly `total`.
+pub fn split_into_periods(total: &BigUR, periods: u32, minor_units: u32) -> Vec<BigUR> {
+    assert!(
+        periods > 0,
+        "There must be at least one period to split into."
+    );
+    let cents = BigUR::from_integer(BigUint::from(10u8).pow(minor_units));
+    let periods_ratio = BigUR::from_integer(BigUint::from(periods));
+    let payment = (total.clone() * cents.clone() / periods_ratio.clone()).floor() / cents;
+    let last = total.clone() - payment.clone() * (periods_ratio - BigUR::one());
+    let mut payments = vec![payment; periods as usize - 1];
+    payments.push(last);
+    debug_assert!(payments.iter().fold(BigUR::zero(), |acc, p| acc + p) == *total);
+    payments
+}
+
+/// Parses a plain decimal like `"0.22"` exactly.
+pub fn parse_decimal(s: &str) -> Option<UR64> {
+    let (whole, fract) = match s.find('.') {
+        Some(idx) => (&s[..idx], &s[idx + 1..]),
+        None => (s, ""),
+    };
+    if whole.is_empty() && fract.is_empty() {
+        return None;
+    }
+    let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+    if !all_digits(whole) || !all_digits(fract) {
+        return None;
+    }
+    let denom = 10u64.checked_pow(fract.len() as u32)?;
+    let numer: u64 = format!("{}{}", whole, fract).parse().ok()?;
+    Some(UR64::new(numer, denom))
+}
+
+/// Parses a rate written as a percentage (`"22%"`), in basis points (`"1023bps"`) or as a decimal
+/// (`"0.22"`) exactly.
+pub fn parse_rate(s: &str) -> Result<UR64, String> {
+    let err = || format!("Could not parse the rate {:?}.", s);
+    let trimmed = s.trim();
+    let (decimal, scale) = if let Some(percentage) = trimmed.strip_suffix('%') {
+        (percentage, 100)
+    } else if let Some(basis_points) = trimmed.strip_suffix("bps") {
+        (basis_points, 10_000)
+    } else {
+        (trimmed, 1)
+    };
+    let decimal = parse_decimal(decimal.trim_end()).ok_or_else(err)?;
+    decimal
+        .checked_div(&UR64::from_integer(scale))
+        .ok_or_else(err)
+}
+
+/// Rounds up to the next multiple of `step`, leaving exact multiples alone.
+pub fn round_up_to(amount: &BigUR, step: &BigUR) -> BigUR {
+    (amount.clone() / step.clone()).ceil() * step.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(dollars: u64, cents: u64) -> BigUR {
+        BigUR::new((dollars * 100 + cents).into(), 100u8.into())
+    }
+
+    #[test]
+    fn biweekly_paychecks_sum_to_the_annual_net_with_the_last_absorbing_the_rounding() {
+        let net = amount(54_321, 9);
+        let paychecks = split_into_periods(&net, 26, 2);
+        assert_eq!(paychecks.len(), 26);
+        assert!(paychecks[..25]
+            .iter()
+            .all(|paycheck| *paycheck == amount(2_089, 27)));
+        assert_eq!(paychecks[25], amount(2_089, 34));
+        assert_eq!(
+            paychecks.into_iter().fold(BigUR::zero(), |acc, p| acc + p),
+            net
+        );
+    }
+
+    #[test]
+    fn suggestions_round_up_to_the_next_thousand() {
+        let thousand = amount(1_000, 0);
+        assert_eq!(
+            round_up_to(&amount(147_382, 91), &thousand),
+            amount(148_000, 0)
+        );
+        assert_eq!(
+            round_up_to(&amount(147_000, 1), &thousand),
+            amount(148_000, 0)
+        );
+        // An exact multiple is already a round figure.
+        assert_eq!(
+            round_up_to(&amount(147_000, 0), &thousand),
+            amount(147_000, 0)
+        );
+    }
+
+    #[test]
+    fn rounding_modes_drop_the_third_decimal_of_an_eighth() {
+        let rounded =
+            |value: UR64, mode| RoundedApproxRatio(value, mode, NumberStyle::Plain).to_string();
+        let eighth = UR64::new(125, 1_000);
+        assert_eq!(rounded(eighth, RoundingMode::Truncate), "0.12 (approx)");
+        assert_eq!(rounded(eighth, RoundingMode::HalfUp), "0.13 (approx)");
+        assert_eq!(rounded(eighth, RoundingMode::HalfEven), "0.12 (approx)");
+        // Half even only rounds up when that's what makes the cents even.
+        assert_eq!(
+            rounded(UR64::new(135, 1_000), RoundingMode::HalfEven),
+            "0.14 (approx)"
+        );
+    }
+
+    #[test]
+    fn number_styles_group_and_separate_the_same_value() {
+        let styled = |style| {
+            RoundedApproxRatio(amount(518_400, 0), RoundingMode::Truncate, style).to_string()
+        };
+        assert_eq!(styled(NumberStyle::Us), "518,400.00 (approx)");
+        assert_eq!(styled(NumberStyle::German), "518.400,00 (approx)");
+        assert_eq!(styled(NumberStyle::Plain), "518400.00 (approx)");
+    }
+
+    #[test]
+    fn quarterly_payments_sum_to_the_annual_tax() {
+        let taxes = amount(26_694, 61);
+        let quarters = split_into_periods(&taxes, 4, 2);
+        assert_eq!(
+            quarters,
+            vec![
+                amount(6_673, 65),
+                amount(6_673, 65),
+                amount(6_673, 65),
+                amount(6_673, 66)
+            ]
+        );
+        assert_eq!(
+            quarters.into_iter().fold(BigUR::zero(), |acc, q| acc + q),
+            taxes
+        );
+    }
+
+    #[test]
+    fn money_is_built_from_dollars_and_cents() {
+        assert_eq!(
+            Money::from_dollars_cents(147_382, 91).0,
+            amount(147_382, 91)
+        );
+        assert_eq!(Money::from_dollars_cents(0, 0), Money::zero());
+        assert_eq!(Money::from_dollars_cents(12, 5).to_string(), "12.05");
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected fewer than 100 cents, not 100.")]
+    fn money_rejects_a_whole_dollar_of_cents() {
+        Money::from_dollars_cents(1, 100);
+    }
+
+    #[test]
+    fn money_adds_subtracts_and_scales() {
+        let mut total = Money::from_dollars_cents(10, 50) + Money::from_dollars_cents(0, 75);
+        assert_eq!(total, Money::from_dollars_cents(11, 25));
+        total += Money::from_dollars_cents(1, 0);
+        assert_eq!(
+            total.clone() - Money::from_dollars_cents(2, 25),
+            Money::from_dollars_cents(10, 0)
+        );
+        assert_eq!(
+            total * BigUR::new(1u8.into(), 5u8.into()),
+            Money::from_dollars_cents(2, 45)
+        );
+        // Displays truncate past the cents.
+        assert_eq!(
+            Money(BigUR::new(1u8.into(), 3u8.into())).to_string(),
+            "0.33"
+        );
+    }
+
+    #[test]
+    fn currency_converts_to_its_exact_amount() {
+        let parse = |s: &str| currency::Currency::from_str(s).unwrap();
+        assert_eq!(currency_to_bigur(&parse("$1234.56")), Ok(amount(1_234, 56)));
+        assert_eq!(currency_to_bigur(&parse("£0.07")), Ok(amount(0, 7)));
+        assert_eq!(currency_to_bigur(&parse("$0")), Ok(BigUR::zero()));
+    }
+
+    #[test]
+    fn negative_currency_is_rejected() {
+        let negative = currency::Currency::from_str("-$5.00").unwrap();
+        assert_eq!(
+            currency_to_bigur(&negative),
+            Err("Expected an amount that isn't negative.".to_owned())
+        );
     }
 }
@@ -0,0 +1,837 @@
+use std::process::{Command, Output};
+
+/// Runs the binary without logging, from outside the repository so `output.log` isn't left there,
+/// and without any of the arguments that fall back to the environment.
+fn equinc(args: &[&str]) -> Output {
+    equinc_with_env(args, &[])
+}
+
+/// Like `equinc`, but with `vars` set in the environment, which can turn the logging back on.
+fn equinc_with_env(args: &[&str], vars: &[(&str, &str)]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_equinc"));
+    for var in [
+        "EQUINC_SOURCE",
+        "EQUINC_TARGET",
+        "EQUINC_INCOME",
+        "EQUINC_MONTHLY_EXPENSES",
+        "EQUINC_STATUS",
+        "EQUINC_LOCATION_DELIMITER",
+    ] {
+        command.env_remove(var);
+    }
+    command
+        .env("EQUINC_LOG", "off")
+        .envs(vars.iter().copied())
+        .args(args)
+        .current_dir(std::env::temp_dir())
+        .output()
+        .expect("the binary to run.")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).expect("the output to be UTF-8.")
+}
+
+/// The amount on the report line starting with `label`, without its symbol or grouping.
+fn figure(output: &Output, label: &str) -> f64 {
+    let stdout = stdout(output);
+    let line = stdout
+        .lines()
+        .find(|line| line.starts_with(label))
+        .unwrap_or_else(|| panic!("Expected a {:?} line in:\n{}", label, stdout));
+    let value = line[line.find(':').unwrap() + 1..]
+        .trim()
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .split(' ')
+        .next()
+        .unwrap()
+        .replace(',', "");
+    value
+        .parse()
+        .unwrap_or_else(|e| panic!("Could not read {:?}: {}", line, e))
+}
+
+const AUSTIN: &str = "USA///TX///Austin";
+const LONDON: &str = "GBR///ENG///London";
+const SF: &str = "USA///CA///SF";
+const PORTLAND: &str = "USA///OR///Portland";
+
+#[test]
+fn target_display_currency_scales_by_the_exchange_rate() {
+    let run = |currency| {
+        equinc(&[
+            AUSTIN,
+            LONDON,
+            "100k",
+            "2k",
+            "single",
+            "--usage",
+            "post_tax",
+            "--display-currency",
+            currency,
+        ])
+    };
+    let (home, target) = (run("home"), run("target"));
+    for label in ["Total earned", "Taxes at home", "Taxes at target"] {
+        let scaled = figure(&home, label) * 0.73;
+        assert!(
+            (figure(&target, label) - scaled).abs() < 0.02,
+            "{} should be {} in pounds",
+            label,
+            scaled
+        );
+    }
+    assert!(stdout(&target).contains("Total earned   : £73,000.00"));
+}
+
+#[test]
+fn amt_is_owed_when_the_qbi_deduction_is_added_back() {
+    let args = [AUSTIN, SF, "160k", "0", "single", "--standard-deduction"];
+    let mut with_qbi = args.to_vec();
+    with_qbi.extend(&["--qbi-income", "160k"]);
+    let mut with_amt = with_qbi.clone();
+    with_amt.push("--amt");
+    let regular = equinc(&with_qbi);
+    let amt = equinc(&with_amt);
+    assert_eq!(amt.status.code(), Some(0));
+    // 26% of the 87,100 above the exemption beats the 21,823.50 left after the deductions.
+    assert_eq!(figure(&regular, "Taxes at home"), 21_823.5);
+    assert_eq!(figure(&amt, "Taxes at home"), 22_646.0);
+    // The state and city taxes stay on top of the national tax at the target.
+    let target = figure(&amt, "Taxes at target") - figure(&regular, "Taxes at target");
+    assert!((target - 822.5).abs() < 1e-6);
+    // Owing more at home lowers the income needed to match it, with no warning that the
+    // equivalent income misses the net.
+    assert!(figure(&amt, "    total") < figure(&regular, "    total"));
+    assert!(String::from_utf8_lossy(&amt.stderr).is_empty());
+    // Without the QBI deduction there's nothing to add back, and the regular tax is higher.
+    let mut plain = args.to_vec();
+    plain.push("--amt");
+    assert_eq!(
+        figure(&equinc(&plain), "Taxes at home"),
+        figure(&equinc(&args), "Taxes at home")
+    );
+}
+
+#[test]
+fn spouse_income_joins_the_taxed_income_when_filing_jointly() {
+    let run = |income, extra: &[&str]| {
+        let mut args = vec![AUSTIN, AUSTIN, income, "0", "joint", "--usage", "post_tax"];
+        args.extend(extra);
+        equinc(&args)
+    };
+    let alone = run("100k", &[]);
+    let together = run("100k", &["--spouse-income", "50k"]);
+    let combined = run("150k", &[]);
+    assert_eq!(figure(&together, "Total earned"), 150_000.0);
+    assert!(figure(&together, "Taxes at home") > figure(&alone, "Taxes at home"));
+    assert_eq!(
+        figure(&together, "Taxes at home"),
+        figure(&combined, "Taxes at home")
+    );
+}
+
+#[test]
+fn spouse_income_is_rejected_when_filing_single() {
+    let output = equinc(&[
+        AUSTIN,
+        AUSTIN,
+        "100k",
+        "0",
+        "single",
+        "--spouse-income",
+        "50k",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Spouse income only applies"));
+}
+
+#[test]
+fn unreadable_extra_tax_file_is_reported_without_panicking() {
+    let output = equinc(&[
+        AUSTIN,
+        AUSTIN,
+        "100k",
+        "0",
+        "single",
+        "--extra-tax-file",
+        "equinc-missing-tax-file.json",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Could not read tax data"));
+}
+
+#[test]
+fn identical_locations_are_noted_and_keep_the_income() {
+    let note = "Note: the home and target locations are the same.";
+    let output = equinc(&[AUSTIN, AUSTIN, "100k", "0", "single", "--usage", "post_tax"]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains(note));
+    assert!(!stdout(&output).contains(note));
+    assert!((figure(&output, "    total") - 100_000.0).abs() < 0.02);
+    // The rendered report starts right away, without the note in front of it.
+    let markdown = equinc(&[
+        AUSTIN, AUSTIN, "100k", "0", "single", "--format", "markdown",
+    ]);
+    assert!(stdout(&markdown).starts_with('|'), "{}", stdout(&markdown));
+    // Anything charged on top at the target makes the two differ after all.
+    for extra in [&["--extra-flat-tax", "1%"][..], &["--work-state", "CA"][..]] {
+        let mut args = vec![AUSTIN, AUSTIN, "100k", "0", "single"];
+        args.extend(extra);
+        let output = equinc(&args);
+        assert_eq!(output.status.code(), Some(0));
+        assert!(
+            !String::from_utf8_lossy(&output.stderr).contains(note),
+            "{:?}",
+            extra
+        );
+    }
+}
+
+#[test]
+fn extra_flat_tax_adds_its_rate_of_the_gross_to_the_target_taxes() {
+    let run = |extra: &[&str]| {
+        let mut args = vec![AUSTIN, SF, "100k", "0", "single", "--usage", "post_tax"];
+        args.extend(extra);
+        equinc(&args)
+    };
+    let added = figure(&run(&["--extra-flat-tax", "1%"]), "Taxes at target")
+        - figure(&run(&[]), "Taxes at target");
+    assert!((added - 1_000.0).abs() < 0.02, "added {}", added);
+}
+
+#[test]
+fn cost_factor_only_prints_the_target_factor_over_the_home_one() {
+    let output = equinc(&[
+        AUSTIN,
+        PORTLAND,
+        "100k",
+        "0",
+        "single",
+        "--cost-factor-only",
+    ]);
+    // Portland's factor of 26073/50000 over Austin's of 19847/50000.
+    assert_eq!(
+        stdout(&output),
+        "Cost of living factor: 1.31 (approx) (26073/19847)\n"
+    );
+}
+
+#[test]
+fn fail_if_above_sets_the_exit_code_by_the_equivalent_income() {
+    // Moving to SF takes about $113,400 to break even on $100,000.
+    let run = |limit| {
+        equinc(&[
+            AUSTIN,
+            SF,
+            "100k",
+            "0",
+            "single",
+            "--usage",
+            "post_tax",
+            "--fail-if-above",
+            limit,
+        ])
+        .status
+        .code()
+    };
+    assert_eq!(run("120k"), Some(0));
+    assert_eq!(run("110k"), Some(2));
+}
+
+#[test]
+fn moving_from_sf_to_austin_shows_a_tax_saving() {
+    let output = equinc(&[SF, AUSTIN, "100k", "0", "single", "--usage", "post_tax"]);
+    let saving = figure(&output, "Taxes at home") - figure(&output, "Taxes at target");
+    assert!(saving > 0.0);
+    let line = stdout(&output)
+        .lines()
+        .find(|line| line.starts_with("Tax change"))
+        .unwrap()
+        .to_owned();
+    assert!(line.contains(": -$"), "{}", line);
+    assert!(line.ends_with("(saving)"), "{}", line);
+    assert!((figure(&output, "Tax change") - saving).abs() < 0.02);
+}
+
+#[test]
+fn half_a_year_of_half_the_salary_is_taxed_in_the_full_year_brackets() {
+    let run = |income, extra: &[&str]| {
+        let mut args = vec![AUSTIN, SF, income, "0", "single", "--usage", "post_tax"];
+        args.extend(extra);
+        equinc(&args)
+    };
+    let partial = run("50k", &["--months-worked", "6"]);
+    let full = run("100k", &[]);
+    assert_eq!(figure(&partial, "Total earned"), 100_000.0);
+    assert_eq!(
+        figure(&partial, "Taxes at home"),
+        figure(&full, "Taxes at home")
+    );
+    // The taxes for the months actually worked are half of the full year's.
+    let half = figure(&full, "Taxes at home") / 2.0;
+    assert!((figure(&partial, "Partial year") - half).abs() < 0.02);
+}
+
+#[test]
+fn explicit_currency_overrides_the_home_country_one() {
+    let paid_in_pounds = equinc(&[
+        AUSTIN,
+        LONDON,
+        "73k",
+        "0",
+        "single",
+        "--usage",
+        "post_tax",
+        "--currency",
+        "GBP",
+    ]);
+    assert!(stdout(&paid_in_pounds).contains("Total earned   : £73,000.00"));
+    // £73,000 is the $100,000 that the home taxes are then worked out on.
+    let paid_in_dollars = equinc(&[AUSTIN, LONDON, "100k", "0", "single", "--usage", "post_tax"]);
+    let scaled = figure(&paid_in_dollars, "Taxes at home") * 0.73;
+    assert!((figure(&paid_in_pounds, "Taxes at home") - scaled).abs() < 0.02);
+    let londoner = equinc(&[LONDON, LONDON, "73k", "0", "single", "--usage", "post_tax"]);
+    assert_eq!(
+        figure(&paid_in_pounds, "Taxes at target"),
+        figure(&londoner, "Taxes at home")
+    );
+}
+
+#[test]
+fn show_schedule_prints_the_merged_top_rate_of_sf() {
+    let output = equinc(&[AUSTIN, SF, "100k", "0", "single", "--show-schedule"]);
+    // 37% federal, 14.63% for California and 1.5% for SF.
+    assert!(stdout(&output).ends_with("Above 1000000.00 (approx): 53.13% (approx)\n"));
+}
+
+#[test]
+fn exchange_rates_from_a_file_or_the_command_line_convert_the_income() {
+    let path = std::env::temp_dir().join("equinc-cli-exchange-rates.json");
+    std::fs::write(&path, r#"{"USD/GBP": "0.5"}"#).unwrap();
+    let run = |extra: &[&str]| {
+        let mut args = vec![
+            AUSTIN,
+            LONDON,
+            "100k",
+            "0",
+            "single",
+            "--usage",
+            "post_tax",
+            "--display-currency",
+            "target",
+        ];
+        args.extend(extra);
+        stdout(&equinc(&args))
+    };
+    let from_file = run(&["--exchange-rate-file", path.to_str().unwrap()]);
+    assert!(
+        from_file.contains("Total earned   : £50,000.00"),
+        "{}",
+        from_file
+    );
+    let overridden = run(&["--exchange-rate", "GBP/USD=2.5"]);
+    assert!(
+        overridden.contains("Total earned   : £40,000.00"),
+        "{}",
+        overridden
+    );
+}
+
+#[test]
+fn hourly_rate_stands_in_for_the_income() {
+    let hourly = |income| {
+        equinc(&[
+            AUSTIN,
+            AUSTIN,
+            income,
+            "0",
+            "single",
+            "--usage",
+            "post_tax",
+            "--hourly-rate",
+            "$50",
+            "--hours-per-week",
+            "40",
+            "--weeks-per-year",
+            "50",
+        ])
+    };
+    assert_eq!(figure(&hourly("0"), "Total earned"), 100_000.0);
+    let both = hourly("100k");
+    assert_eq!(both.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&both.stderr).contains("--hourly-rate replaces the income"));
+}
+
+#[test]
+fn employer_match_raises_total_comp_but_not_taxes() {
+    let run = |extra: &[&str]| {
+        let mut args = vec![AUSTIN, SF, "100k", "0", "single", "--usage", "post_tax"];
+        args.extend(extra);
+        equinc(&args)
+    };
+    let plain = run(&[]);
+    let matched = run(&["--employer-match", "5k"]);
+    assert_eq!(figure(&matched, "Total comp"), 105_000.0);
+    for label in ["Total earned", "Taxes at home", "Taxes at target"] {
+        assert_eq!(figure(&matched, label), figure(&plain, label), "{}", label);
+    }
+}
+
+#[test]
+fn compare_statuses_ranks_all_four_by_their_taxes() {
+    let output = equinc(&[SF, AUSTIN, "100k", "0", "single", "--compare-statuses"]);
+    assert_eq!(
+        stdout(&output),
+        "1. Joint: 13580.00 (approx)\n\
+         2. HeadOfHousehold: 16638.00 (approx)\n\
+         3. Single: 18079.50 (approx)\n\
+         4. Separate: 18079.50 (approx)\n"
+    );
+}
+
+#[test]
+fn income_entered_in_euros_is_shown_in_euros() {
+    let output = equinc(&[
+        AUSTIN, LONDON, "€100k", "0", "single", "--usage", "post_tax",
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains("Total earned   : €100,000.00"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains('$') && !stdout.contains('£'), "{}", stdout);
+}
+
+#[test]
+fn batch_gives_back_a_row_for_each_scenario() {
+    let path = std::env::temp_dir().join("equinc-cli-batch.csv");
+    std::fs::write(
+        &path,
+        "income,status,source,target\n\
+         100000,single,USA///TX///Austin,USA///CA///SF\n\
+         50000,joint,USA///CA///SF,USA///TX///Austin\n\
+         73000,single,GBR///ENG///London,GBR///ENG///London\n",
+    )
+    .unwrap();
+    let output = equinc(&["batch", "--input-csv", path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "income,status,source,target,equivalent_income,home_taxes,target_taxes\n\
+         100000.00,single,USA///TX///Austin,USA///CA///SF,113404.55,18079.50,26694.60\n\
+         50000.00,joint,USA///CA///SF,USA///TX///Austin,47912.10,7442.35,5605.00\n\
+         73000.00,single,GBR///ENG///London,GBR///ENG///London,73000.00,16700.00,16700.00\n"
+    );
+}
+
+#[test]
+fn batch_reports_a_negative_income_with_its_row() {
+    let path = std::env::temp_dir().join("equinc-cli-batch-negative.csv");
+    std::fs::write(
+        &path,
+        "income,status,source,target\n\
+         -5000,single,USA///TX///Austin,USA///CA///SF\n",
+    )
+    .unwrap();
+    let output = equinc(&["batch", "--input-csv", path.to_str().unwrap()]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("Row 1: "), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn environment_fills_in_the_arguments_left_out() {
+    let vars = [
+        ("EQUINC_SOURCE", AUSTIN),
+        ("EQUINC_TARGET", SF),
+        ("EQUINC_INCOME", "100k"),
+        ("EQUINC_MONTHLY_EXPENSES", "0"),
+        ("EQUINC_STATUS", "single"),
+    ];
+    let from_env = equinc_with_env(&["--usage", "post_tax"], &vars);
+    assert_eq!(from_env.status.code(), Some(0));
+    assert_eq!(figure(&from_env, "Total earned"), 100_000.0);
+    assert!(stdout(&from_env).contains("Moving to      : SF, California"));
+    // The arguments given still win over the environment.
+    let overridden = equinc_with_env(
+        &[AUSTIN, AUSTIN, "50k", "0", "single", "--usage", "post_tax"],
+        &vars,
+    );
+    assert_eq!(figure(&overridden, "Total earned"), 50_000.0);
+    assert!(stdout(&overridden).contains("Moving to      : Austin, Texas"));
+}
+
+#[test]
+fn summary_only_prints_just_the_equivalent_income() {
+    let output = equinc(&[
+        AUSTIN,
+        SF,
+        "100k",
+        "0",
+        "single",
+        "--usage",
+        "post_tax",
+        "--summary-only",
+    ]);
+    assert_eq!(stdout(&output), "$113,404.55 (approx) (728851058/6427)\n");
+}
+
+#[test]
+fn net_per_hour_is_the_target_net_over_the_annual_hours() {
+    let output = equinc(&[
+        AUSTIN,
+        SF,
+        "100k",
+        "0",
+        "single",
+        "--usage",
+        "post_tax",
+        "--hours-per-year",
+        "2000",
+    ]);
+    // The equivalent income nets at the target what the income does at home.
+    let net = figure(&output, "Total earned") - figure(&output, "Taxes at home");
+    assert!((figure(&output, "Net per hour") - net / 2000.0).abs() < 0.01);
+    assert!(stdout(&output).contains("Net per hour   : $40.96"));
+}
+
+#[test]
+fn json_log_lines_parse_as_json() {
+    let output = equinc_with_env(
+        &[AUSTIN, SF, "100k", "0", "single", "--log-format", "json"],
+        &[("EQUINC_LOG", "info")],
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = stdout(&output);
+    // Only debug builds log to the standard output, rather than just to the file.
+    let log_lines: Vec<_> = stdout
+        .lines()
+        .filter(|line| line.starts_with('{'))
+        .collect();
+    assert_eq!(!log_lines.is_empty(), cfg!(debug_assertions), "{}", stdout);
+    for line in log_lines {
+        let entry: serde_json::Value =
+            serde_json::from_str(line).unwrap_or_else(|e| panic!("{:?} is not JSON: {}", line, e));
+        for field in ["timestamp", "level", "target", "message"] {
+            assert!(entry[field].is_string(), "{:?} has no {}", line, field);
+        }
+    }
+}
+
+#[test]
+fn bonus_is_withheld_at_the_supplemental_rate_but_owed_at_the_marginal_one() {
+    let output = equinc(&[
+        AUSTIN, AUSTIN, "100k", "0", "single", "--usage", "post_tax", "--bonus", "10k",
+    ]);
+    assert_eq!(figure(&output, "Total earned"), 110_000.0);
+    // 22% is withheld, but the whole bonus lands in the 24% band.
+    assert!(stdout(&output).contains(
+        "Bonus taxes    : $2,200.00 (approx) withheld, $2,400.00 (approx) owed at target"
+    ));
+}
+
+#[test]
+fn offer_above_the_equivalent_income_is_a_gain() {
+    let run = |offer| {
+        let output = equinc(&[
+            AUSTIN, SF, "100k", "0", "single", "--usage", "post_tax", "--offer", offer,
+        ]);
+        stdout(&output)
+            .lines()
+            .find(|line| line.starts_with("Offer"))
+            .unwrap()
+            .to_owned()
+    };
+    // Breaking even takes about $113,404.55.
+    let above = run("120k");
+    assert!(
+        above.contains("+$6,595.44 (approx) over the equivalent income (a gain)"),
+        "{}",
+        above
+    );
+    let below = run("110k");
+    assert!(
+        below.contains("under the equivalent income (a loss)"),
+        "{}",
+        below
+    );
+}
+
+#[test]
+fn two_earners_are_analyzed_as_one_joint_household() {
+    let couple = equinc(&[AUSTIN, SF, "75k", "2k", "joint", "--spouse-income", "75k"]);
+    let household = "Household      : $75,000.00 (approx) + $75,000.00 (approx), filing jointly\n";
+    assert!(stdout(&couple).contains(household));
+    // Sharing the expenses, they need what a single $150,000 earner filing jointly does.
+    let single_earner = equinc(&[AUSTIN, SF, "150k", "2k", "joint"]);
+    assert_eq!(
+        stdout(&couple).replace(household, ""),
+        stdout(&single_earner)
+    );
+}
+
+#[test]
+fn including_fica_adds_the_payroll_taxes_to_the_income_tax() {
+    let run = |extra: &[&str]| {
+        let mut args = vec![AUSTIN, SF, "100k", "0", "single", "--usage", "post_tax"];
+        args.extend(extra);
+        figure(&equinc(&args), "Taxes at home")
+    };
+    let baseline = run(&[]);
+    // 6.2% for Social Security and 1.45% for Medicare, since $100,000 is under the wage base.
+    assert_eq!(run(&["--include", "fica"]), baseline + 7_650.0);
+    assert_eq!(run(&["--include", ""]), baseline);
+}
+
+#[test]
+fn precision_exact_refuses_figures_that_dont_end_at_the_cent() {
+    let run = |home, target, income| {
+        equinc(&[
+            home,
+            target,
+            income,
+            "0",
+            "single",
+            "--usage",
+            "post_tax",
+            "--precision-exact",
+        ])
+    };
+    // Breaking even in SF takes 728851058/6427, which never ends.
+    let inexact = run(AUSTIN, SF, "100k");
+    assert_eq!(inexact.status.code(), Some(1));
+    assert!(stdout(&inexact).is_empty());
+    assert!(String::from_utf8_lossy(&inexact.stderr)
+        .contains("728851058/6427 (113404.55 (approx)) doesn't end at the cent"));
+    // Everything about London's taxes on £100,000 is exact, so nothing is approximated.
+    let exact = run(LONDON, LONDON, "100k");
+    assert_eq!(exact.status.code(), Some(0));
+    assert!(!stdout(&exact).contains("(approx)"));
+    assert!(stdout(&exact).contains("Taxes at home  : £27,500.00\n"));
+}
+
+#[test]
+fn already_taxable_income_reaches_the_brackets_without_deductions() {
+    let run = |extra: &[&str]| {
+        let mut args = vec![
+            AUSTIN,
+            AUSTIN,
+            "100k",
+            "0",
+            "single",
+            "--usage",
+            "post_tax",
+            "--income-is-taxable",
+        ];
+        args.extend(extra);
+        equinc(&args)
+    };
+    // 10% of 9,875, 12% of 30,250, 22% of 45,400 and 24% of the 14,475 above 85,525.
+    assert_eq!(figure(&run(&[]), "Taxes at home"), 18_079.5);
+    for deduction in [
+        &["--salt"][..],
+        &["--standard-deduction"],
+        &["--hsa-contribution", "1k"],
+    ] {
+        let output = run(deduction);
+        assert_eq!(output.status.code(), Some(1), "{:?}", deduction);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+    }
+}
+
+#[test]
+fn all_states_ranks_texas_above_california() {
+    let output = equinc(&[AUSTIN, SF, "100k", "0", "single", "--all-states"]);
+    let stdout = stdout(&output);
+    let rank = |state: &str| {
+        stdout
+            .lines()
+            .position(|line| line.contains(&format!(" {} (", state)))
+            .unwrap_or_else(|| panic!("{} is missing from:\n{}", state, stdout))
+    };
+    assert_eq!(rank("TX"), 0);
+    assert!(rank("TX") < rank("CA"));
+    // Texas has no income tax, so only the federal one is owed.
+    assert!(stdout.starts_with("1. TX (Texas, United States of America): 18079.50 (approx)\n"));
+}
+
+#[test]
+fn location_delimiter_comes_from_the_environment() {
+    let output = equinc_with_env(
+        &[
+            "USA|TX|Austin",
+            "USA|CA|SF",
+            "100k",
+            "0",
+            "single",
+            "--usage",
+            "post_tax",
+        ],
+        &[("EQUINC_LOCATION_DELIMITER", "|")],
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains("Moving to      : SF, California"));
+}
+
+#[test]
+fn dumped_sf_schedule_sums_the_federal_state_and_city_rates_in_each_band() {
+    let output = equinc(&[AUSTIN, SF, "100k", "0", "single", "--dump-merged"]);
+    let stdout = stdout(&output);
+    // Each schedule's bands as where they start and their rate in percent.
+    let schedule = |name: &str| -> Vec<(f64, f64)> {
+        let number = |s: &str| s.trim_start_matches("Above ").parse::<f64>().unwrap();
+        stdout
+            .lines()
+            .skip_while(|line| *line != format!("{} brackets for Single:", name))
+            .skip(1)
+            .take_while(|line| !line.is_empty() && !line.ends_with("for Single:"))
+            .map(|line| {
+                let (band, rate) = line.split_once(": ").unwrap();
+                let floor = number(band.split(" (approx)").next().unwrap());
+                (floor, number(rate.split('%').next().unwrap()))
+            })
+            .collect()
+    };
+    let rate_at = |bands: &[(f64, f64)], income: f64| {
+        bands
+            .iter()
+            .rev()
+            .find(|(floor, _)| *floor <= income)
+            .unwrap()
+            .1
+    };
+    let parts = [schedule("USA"), schedule("CA"), schedule("SF")];
+    let merged = schedule("Merged");
+    assert_eq!(merged.len(), 16);
+    for (floor, rate) in merged {
+        let sum: f64 = parts.iter().map(|part| rate_at(part, floor)).sum();
+        assert_eq!(
+            (sum * 100.0).round(),
+            (rate * 100.0).round(),
+            "above {}",
+            floor
+        );
+    }
+    assert!(stdout.contains("85525.00 (approx) to 163300.00 (approx): 35.73% (approx)\n"));
+}
+
+#[test]
+fn status_missing_from_a_tax_file_is_warned_about_rather_than_silently_untaxed() {
+    let path = std::env::temp_dir().join("equinc-cli-single-only.json");
+    std::fs::write(&path, r#"{"single": {"separators": [], "rates": ["1%"]}}"#).unwrap();
+    let run = |status, extra: &[&str]| {
+        let mut args = vec![
+            AUSTIN,
+            AUSTIN,
+            "100k",
+            "0",
+            status,
+            "--usage",
+            "post_tax",
+            "--extra-tax-file",
+            path.to_str().unwrap(),
+        ];
+        args.extend(extra);
+        equinc_with_env(&args, &[("EQUINC_LOG", "warn")])
+    };
+    let missing = "No brackets for Joint in extra #1 at USA///TX///Austin.";
+    let joint = run("joint", &[]);
+    assert_eq!(joint.status.code(), Some(0));
+    // Only debug builds log to the standard output, rather than just to the file.
+    assert_eq!(stdout(&joint).contains(missing), cfg!(debug_assertions));
+    let strict = run("joint", &["--strict-statuses"]);
+    assert_eq!(strict.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&strict.stderr).contains(missing));
+    // The file has brackets for single filers, so there's nothing to warn about.
+    let single = run("single", &["--strict-statuses"]);
+    assert_eq!(single.status.code(), Some(0));
+    assert!(!stdout(&single).contains("No brackets"));
+}
+
+#[test]
+fn decoding_an_encoded_scenario_reproduces_its_report() {
+    let args = [AUSTIN, SF, "100k", "2k", "single", "--fica"];
+    let mut encoding = args.to_vec();
+    encoding.push("--encode");
+    let scenario = stdout(&equinc(&encoding)).trim().to_owned();
+    let decoded = equinc(&["decode", &scenario]);
+    assert_eq!(decoded.status.code(), Some(0));
+    assert_eq!(stdout(&decoded), stdout(&equinc(&args)));
+}
+
+#[test]
+fn encoded_scenarios_keep_the_arguments_from_the_environment() {
+    let vars = [
+        ("EQUINC_SOURCE", "USA|TX|Austin"),
+        ("EQUINC_TARGET", "USA|CA|SF"),
+        ("EQUINC_INCOME", "100k"),
+        ("EQUINC_MONTHLY_EXPENSES", "2k"),
+        ("EQUINC_STATUS", "single"),
+        ("EQUINC_LOCATION_DELIMITER", "|"),
+    ];
+    let encoded = equinc_with_env(&["--fica", "--encode"], &vars);
+    assert_eq!(encoded.status.code(), Some(0));
+    let scenario = stdout(&encoded).trim().to_owned();
+    // Decoded without any of the environment, or its delimiter.
+    let decoded = equinc(&["decode", &scenario]);
+    assert_eq!(decoded.status.code(), Some(0));
+    assert_eq!(
+        stdout(&decoded),
+        stdout(&equinc(&[AUSTIN, SF, "100k", "2k", "single", "--fica"]))
+    );
+}
+
+#[test]
+fn moving_to_london_scales_the_expenses_by_its_living_costs() {
+    let output = equinc(&[AUSTIN, LONDON, "100k", "2k", "single"]);
+    assert_eq!(output.status.code(), Some(0));
+    // London costs more than Austin, so keeping the same disposable income takes more than
+    // keeping the same take-home.
+    let post_tax = equinc(&[
+        AUSTIN, LONDON, "100k", "2k", "single", "--usage", "post_tax",
+    ]);
+    assert!(figure(&output, "    total") > figure(&post_tax, "    total"));
+}
+
+#[test]
+fn negative_amounts_are_rejected_without_panicking() {
+    for flag in [
+        "--spouse-income=-5k",
+        "--bonus=-5k",
+        "--offer=-5k",
+        "--employer-match=-5k",
+        "--fail-if-above=-5k",
+    ] {
+        let output = equinc(&[AUSTIN, SF, "100k", "0", "joint", flag]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(1), "{}", flag);
+        assert!(stderr.contains("isn't negative"), "{}: {}", flag, stderr);
+        assert!(!stderr.contains("panicked"), "{}: {}", flag, stderr);
+    }
+}
+
+#[test]
+fn split_year_taxes_at_either_end_are_the_target_and_home_taxes() {
+    let all_at_target = equinc(&[AUSTIN, SF, "150k", "0", "single", "--split-year", "0%"]);
+    assert_eq!(
+        figure(&all_at_target, "Split-year tax"),
+        figure(&all_at_target, "Taxes at target")
+    );
+    let all_at_home = equinc(&[
+        SF,
+        SF,
+        "150k",
+        "0",
+        "single",
+        "--split-year",
+        "100%",
+        "--standard-deduction",
+    ]);
+    assert_eq!(
+        figure(&all_at_home, "Split-year tax"),
+        figure(&all_at_home, "Taxes at home")
+    );
+}